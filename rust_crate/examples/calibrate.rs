@@ -0,0 +1,198 @@
+// Copyright (C) 2021-2025 the DTVM authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Calibrate per-opcode gas weights against the DTVM interpreter.
+//!
+//! Every probe runs one opcode many times in a loop through `ZenRuntime` in interpreter mode
+//! and subtracts the time of the same loop without the opcode. Weights are the measured time
+//! relative to `i32.add`, written as a cost table that loads with `TableCostRules::from_toml`.
+//!
+//! The weight of a class is the highest weight of its probed opcodes. Classes without a probe
+//! are left out of the table: they fall back to its `default_cost` once one is added, and are
+//! forbidden until then, so the table never prices them by guess.
+//!
+//! Usage: `cargo run --release --example calibrate > cost_profile.toml`
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use dtvmcore_rust::gas_metering::{InstructionClass, TableCostRules};
+use dtvmcore_rust::prelude::*;
+use InstructionClass::{Arithmetic, Division, Multiplication};
+
+const ITERATIONS: i32 = 20000;
+const UNROLL: usize = 16;
+const RUNS: usize = 3;
+const REFERENCE_OPCODE: &str = "i32.add";
+
+struct Probe {
+    opcode: &'static str,
+    class: InstructionClass,
+    value_type: &'static str,
+    arity: usize,
+    // operand expressions use the address local instead of the value locals
+    memory_access: bool,
+}
+
+const fn probe(
+    opcode: &'static str,
+    class: InstructionClass,
+    value_type: &'static str,
+    arity: usize,
+) -> Probe {
+    Probe {
+        opcode,
+        class,
+        value_type,
+        arity,
+        memory_access: false,
+    }
+}
+
+const fn load_probe(opcode: &'static str, value_type: &'static str) -> Probe {
+    Probe {
+        opcode,
+        class: InstructionClass::MemoryLoad,
+        value_type,
+        arity: 1,
+        memory_access: true,
+    }
+}
+
+const PROBES: &[Probe] = &[
+    probe("i32.add", Arithmetic, "i32", 2),
+    probe("i32.sub", Arithmetic, "i32", 2),
+    probe("i32.mul", Multiplication, "i32", 2),
+    probe("i32.div_s", Division, "i32", 2),
+    probe("i32.div_u", Division, "i32", 2),
+    probe("i32.rem_s", Division, "i32", 2),
+    probe("i32.rem_u", Division, "i32", 2),
+    probe("i32.and", Arithmetic, "i32", 2),
+    probe("i32.or", Arithmetic, "i32", 2),
+    probe("i32.xor", Arithmetic, "i32", 2),
+    probe("i32.shl", Arithmetic, "i32", 2),
+    probe("i32.shr_s", Arithmetic, "i32", 2),
+    probe("i32.shr_u", Arithmetic, "i32", 2),
+    probe("i32.rotl", Arithmetic, "i32", 2),
+    probe("i32.eq", Arithmetic, "i32", 2),
+    probe("i32.lt_s", Arithmetic, "i32", 2),
+    probe("i32.clz", Arithmetic, "i32", 1),
+    probe("i32.ctz", Arithmetic, "i32", 1),
+    probe("i32.popcnt", Arithmetic, "i32", 1),
+    probe("i32.eqz", Arithmetic, "i32", 1),
+    probe("i64.add", Arithmetic, "i64", 2),
+    probe("i64.mul", Multiplication, "i64", 2),
+    probe("i64.div_s", Division, "i64", 2),
+    probe("i64.rem_u", Division, "i64", 2),
+    probe("i64.shl", Arithmetic, "i64", 2),
+    probe("i64.eq", Arithmetic, "i64", 2),
+    probe("i64.clz", Arithmetic, "i64", 1),
+    probe("i64.popcnt", Arithmetic, "i64", 1),
+    load_probe("i32.load", "i32"),
+    load_probe("i64.load", "i64"),
+    load_probe("i32.load8_u", "i32"),
+];
+
+/// Build a module whose exported `run(iterations)` executes `snippet` `UNROLL` times per loop
+fn probe_module(value_type: &str, snippet: &str) -> Vec<u8> {
+    let body = snippet.repeat(UNROLL);
+    let wat = format!(
+        r#"
+        (module
+            (memory 1)
+            (func (export "run") (param $iters i32)
+                (local $x {value_type}) (local $y {value_type}) (local $p i32)
+                (local.set $x ({value_type}.const 7))
+                (local.set $y ({value_type}.const 3))
+                (block $done
+                    (loop $next
+                        (br_if $done (i32.eqz (local.get $iters)))
+                        {body}
+                        (local.set $iters (i32.sub (local.get $iters) (i32.const 1)))
+                        (br $next)
+                    )
+                )
+            )
+        )
+    "#
+    );
+    wat::parse_str(wat).expect("Failed to parse probe WAT")
+}
+
+/// The snippet running the opcode, and a baseline pushing the same operands and dropping one
+/// value, so the two only differ by the opcode. Operands the baseline doesn't drop stay on the
+/// stack until the loop branches back.
+fn probe_snippets(probe: &Probe) -> (String, String) {
+    let operands: Vec<&str> = if probe.memory_access {
+        vec!["(local.get $p)"]
+    } else {
+        ["(local.get $x)", "(local.get $y)"][..probe.arity].to_vec()
+    };
+    let with_opcode = format!("(drop ({} {}))", probe.opcode, operands.join(" "));
+    let baseline = format!("{} drop", operands.join(" "));
+    (with_opcode, baseline)
+}
+
+/// Best wall time of `RUNS` executions of the probe module
+fn measure(rt: &Rc<ZenRuntime>, wasm_bytes: &[u8]) -> Result<Duration, String> {
+    let wasm_mod = rt.load_module_from_bytes("calibrate.wasm", wasm_bytes)?;
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let isolation = rt.new_isolation()?;
+        let inst = wasm_mod.new_instance(isolation, u64::MAX)?;
+        let start = Instant::now();
+        inst.call_wasm_func("run", &[ZenValue::ZenI32Value(ITERATIONS)])?;
+        best = best.min(start.elapsed());
+    }
+    Ok(best)
+}
+
+fn main() -> Result<(), String> {
+    let rt = ZenRuntime::new(Some(ZenRuntimeMode::Interp));
+    let executed_ops = (ITERATIONS as f64) * (UNROLL as f64);
+
+    let mut per_op_nanos: Vec<(&Probe, f64)> = vec![];
+    for probe in PROBES {
+        let (with_opcode, baseline) = probe_snippets(probe);
+        let with_opcode_time = measure(&rt, &probe_module(probe.value_type, &with_opcode))?;
+        let baseline_time = measure(&rt, &probe_module(probe.value_type, &baseline))?;
+        let delta = with_opcode_time.saturating_sub(baseline_time);
+        per_op_nanos.push((probe, delta.as_nanos() as f64 / executed_ops));
+    }
+
+    let reference_nanos = per_op_nanos
+        .iter()
+        .find(|(probe, _)| probe.opcode == REFERENCE_OPCODE)
+        .map(|(_, nanos)| *nanos)
+        .filter(|nanos| *nanos > 0.0)
+        .ok_or_else(|| format!("{REFERENCE_OPCODE} probe measured no time"))?;
+
+    let mut class_costs: BTreeMap<InstructionClass, u32> = BTreeMap::new();
+    for (probe, nanos) in &per_op_nanos {
+        let weight = (nanos / reference_nanos).round().max(1.0) as u32;
+        let cost = class_costs.entry(probe.class).or_default();
+        *cost = (*cost).max(weight);
+    }
+    let unprobed: Vec<String> = InstructionClass::ALL
+        .iter()
+        .filter(|class| !class_costs.contains_key(*class))
+        .map(|class| format!("{class:?}"))
+        .collect();
+    let table = TableCostRules::new(class_costs, 0, 1)
+        .to_toml()
+        .map_err(|err| err.to_string())?;
+
+    println!("# Generated by `cargo run --release --example calibrate`.");
+    println!("# Weights are relative to {REFERENCE_OPCODE} on the interpreter. Unprobed classes");
+    println!(
+        "# are forbidden unless a default_cost is added: {}.",
+        unprobed.join(", ")
+    );
+    println!("# Measured opcodes:");
+    for (probe, nanos) in &per_op_nanos {
+        println!("#   {} ({:?}): {nanos:.3} ns", probe.opcode, probe.class);
+    }
+    print!("{table}");
+    Ok(())
+}
@@ -1,7 +1,6 @@
 // Copyright (C) 2021-2025 the DTVM authors. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
-use dtvmcore_rust::core::runtime::ZenRuntime;
-use dtvmcore_rust::core::types::ZenValue;
+use dtvmcore_rust::prelude::*;
 
 fn main() {
     println!("hello world, this is ZetaEngine rust example");
@@ -0,0 +1,126 @@
+// Copyright (C) 2021-2025 the DTVM authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Host ABI version negotiation.
+//!
+//! A contract declares the host ABI version it was compiled against with a custom section named
+//! [`HOST_ABI_VERSION_SECTION`] whose payload is the version as a 4-byte little-endian `u32`.
+//! The runtime compares it with the versions it supports before handing the module to the
+//! engine, so host function signatures can evolve without silently breaking deployed contracts.
+//! Modules without the section are treated as legacy modules and are always accepted.
+
+use std::ops::RangeInclusive;
+
+/// Name of the custom section holding the targeted host ABI version.
+pub const HOST_ABI_VERSION_SECTION: &str = "host_abi_version";
+
+/// The host ABI version implemented by this crate.
+pub const CURRENT_HOST_ABI_VERSION: u32 = 1;
+
+pub(crate) const WASM_MAGIC: &[u8; 4] = b"\0asm";
+const CUSTOM_SECTION_ID: u8 = 0;
+
+pub(crate) fn read_leb_u32(bytes: &[u8], pos: &mut usize) -> Result<u32, String> {
+    let mut result: u32 = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *bytes
+            .get(*pos)
+            .ok_or_else(|| "unexpected end of wasm while reading leb128".to_string())?;
+        *pos += 1;
+        if shift == 28 && byte > 0x0f {
+            return Err("invalid leb128 u32".to_string());
+        }
+        result |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+        shift += 7;
+    }
+}
+
+/// Read the host ABI version declared by a wasm module.
+///
+/// Only the section headers are scanned, so this works for any module the engine can load,
+/// regardless of which wasm proposals it uses. Returns `Ok(None)` when the module does not
+/// declare a version.
+pub fn read_host_abi_version(code: &[u8]) -> Result<Option<u32>, String> {
+    if code.len() < 8 || &code[0..4] != WASM_MAGIC {
+        return Err("invalid wasm magic".to_string());
+    }
+    let mut pos = 8;
+    while pos < code.len() {
+        let section_id = code[pos];
+        pos += 1;
+        let section_size = read_leb_u32(code, &mut pos)? as usize;
+        let section_end = pos
+            .checked_add(section_size)
+            .filter(|end| *end <= code.len())
+            .ok_or_else(|| "wasm section exceeds module size".to_string())?;
+        if section_id == CUSTOM_SECTION_ID {
+            let mut name_pos = pos;
+            let name_len = read_leb_u32(code, &mut name_pos)? as usize;
+            let name_end = name_pos
+                .checked_add(name_len)
+                .filter(|end| *end <= section_end)
+                .ok_or_else(|| "custom section name exceeds section size".to_string())?;
+            if &code[name_pos..name_end] == HOST_ABI_VERSION_SECTION.as_bytes() {
+                let payload = &code[name_end..section_end];
+                if payload.len() != 4 {
+                    return Err(format!(
+                        "invalid {HOST_ABI_VERSION_SECTION} section: expect 4 bytes, got {}",
+                        payload.len()
+                    ));
+                }
+                let version = u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]);
+                return Ok(Some(version));
+            }
+        }
+        pos = section_end;
+    }
+    Ok(None)
+}
+
+/// Check the version declared by `code` against the `supported` host ABI versions.
+pub fn check_host_abi_version(code: &[u8], supported: &RangeInclusive<u32>) -> Result<(), String> {
+    match read_host_abi_version(code)? {
+        Some(version) if !supported.contains(&version) => Err(format!(
+            "host abi version mismatch: module targets v{version}, runtime supports v{}..=v{}",
+            supported.start(),
+            supported.end()
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_host_abi_version() {
+        let wasm = wat::parse_str(r#"(module (@custom "host_abi_version" "\02\00\00\00"))"#)
+            .expect("Failed to parse WAT");
+        assert_eq!(Some(2), read_host_abi_version(&wasm).unwrap());
+
+        let legacy = wat::parse_str("(module)").expect("Failed to parse WAT");
+        assert_eq!(None, read_host_abi_version(&legacy).unwrap());
+
+        let malformed = wat::parse_str(r#"(module (@custom "host_abi_version" "\02"))"#)
+            .expect("Failed to parse WAT");
+        assert!(read_host_abi_version(&malformed).is_err());
+        assert!(read_host_abi_version(b"invalid wasm bytes").is_err());
+    }
+
+    #[test]
+    fn test_check_host_abi_version() {
+        let wasm = wat::parse_str(r#"(module (@custom "host_abi_version" "\02\00\00\00"))"#)
+            .expect("Failed to parse WAT");
+        assert!(check_host_abi_version(&wasm, &(1..=2)).is_ok());
+        let err = check_host_abi_version(&wasm, &(1..=1)).unwrap_err();
+        assert!(err.contains("host abi version mismatch"));
+
+        let legacy = wat::parse_str("(module)").expect("Failed to parse WAT");
+        assert!(check_host_abi_version(&legacy, &(1..=1)).is_ok());
+    }
+}
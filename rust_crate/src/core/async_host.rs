@@ -0,0 +1,90 @@
+// Copyright (C) 2021-2025 the DTVM authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Calling async code from host functions.
+//!
+//! Host functions run synchronously inside the engine, which cannot suspend a wasm call, so an
+//! async host (e.g. one fetching state from a remote node) has to block the calling thread
+//! until its future completes. [`AsyncHostAdapter::block_on`] does that on a tokio runtime and
+//! is meant to be used inside closures registered with
+//! [`ZenRuntime::register_host_closure`](super::runtime::ZenRuntime::register_host_closure):
+//!
+//! ```ignore
+//! let adapter = AsyncHostAdapter::new(tokio_runtime.handle().clone());
+//! let get_balance = rt.register_host_closure(
+//!     "env",
+//!     "get_balance",
+//!     move |inst: &ZenInstance<Ctx>, addr: i32| -> Result<i64, HostFunctionError> {
+//!         adapter.block_on(fetch_balance(inst, addr))?
+//!     },
+//! )?;
+//! ```
+
+use std::future::Future;
+
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+use super::host_module::HostFunctionError;
+
+/// Runs futures to completion from host functions on a tokio runtime.
+#[derive(Clone, Debug)]
+pub struct AsyncHostAdapter {
+    handle: Handle,
+}
+
+impl AsyncHostAdapter {
+    pub fn new(handle: Handle) -> Self {
+        Self { handle }
+    }
+
+    /// Adapter for the tokio runtime of the current thread, if any.
+    pub fn current() -> Option<Self> {
+        Handle::try_current().ok().map(Self::new)
+    }
+
+    /// Block the calling host function until `fut` completes.
+    ///
+    /// On a worker thread of a multi-threaded runtime the worker is handed over to other tasks
+    /// while blocking. On a current-thread runtime blocking would deadlock (and tokio panics,
+    /// which aborts across the FFI boundary), so [`HostFunctionError::Abort`] is returned
+    /// instead: run wasm from `spawn_blocking` or a plain thread in that case.
+    pub fn block_on<F: Future>(&self, fut: F) -> Result<F::Output, HostFunctionError> {
+        match Handle::try_current() {
+            Ok(current) => match current.runtime_flavor() {
+                RuntimeFlavor::MultiThread => {
+                    Ok(tokio::task::block_in_place(|| self.handle.block_on(fut)))
+                }
+                _ => Err(HostFunctionError::Abort),
+            },
+            Err(_) => Ok(self.handle.block_on(fut)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::runtime::Builder;
+
+    #[test]
+    fn test_block_on_outside_runtime() {
+        let runtime = Builder::new_multi_thread().build().unwrap();
+        let adapter = AsyncHostAdapter::new(runtime.handle().clone());
+        assert_eq!(Ok(42), adapter.block_on(async { 42 }));
+    }
+
+    #[test]
+    fn test_block_on_inside_runtime() {
+        let runtime = Builder::new_multi_thread().build().unwrap();
+        let result = runtime.block_on(async {
+            let adapter = AsyncHostAdapter::current().unwrap();
+            adapter.block_on(async { 7 })
+        });
+        assert_eq!(Ok(7), result);
+
+        let current_thread = Builder::new_current_thread().build().unwrap();
+        let adapter = AsyncHostAdapter::new(current_thread.handle().clone());
+        let result = current_thread.block_on(async { adapter.block_on(async { 7 }) });
+        assert_eq!(Err(HostFunctionError::Abort), result);
+    }
+}
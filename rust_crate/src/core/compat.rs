@@ -0,0 +1,322 @@
+// Copyright (C) 2021-2025 the DTVM authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Compatibility report for a contract, the first thing to run when a module fails to load.
+//!
+//! [`analyze_contract_compat`] lists the wasm proposals the module uses and flags the ones the
+//! engine can't run, checks its function imports against the registered host functions and
+//! tries gas metering on it, with a suggestion for every problem found.
+
+use super::host_module::ZenHostFuncSignature;
+use super::types::ZenValueType;
+use crate::gas_metering::{instruction_class, GasMeter, InstructionClass};
+use parity_wasm::elements::{self, Instruction, ValueType};
+use std::fmt;
+
+/// A post-MVP wasm proposal used by a module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WasmProposal {
+    SignExtension,
+    BulkMemory,
+    Simd,
+    MultiValue,
+    MutableGlobals,
+}
+
+impl WasmProposal {
+    /// Whether the engine can run modules using the proposal. It decodes neither the `0xFC`
+    /// bulk memory nor the `0xFD` simd opcodes.
+    pub fn is_supported(&self) -> bool {
+        !matches!(self, WasmProposal::BulkMemory | WasmProposal::Simd)
+    }
+}
+
+impl fmt::Display for WasmProposal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WasmProposal::SignExtension => write!(f, "sign-extension"),
+            WasmProposal::BulkMemory => write!(f, "bulk-memory"),
+            WasmProposal::Simd => write!(f, "simd"),
+            WasmProposal::MultiValue => write!(f, "multi-value"),
+            WasmProposal::MutableGlobals => write!(f, "mutable-globals"),
+        }
+    }
+}
+
+/// Result of [`analyze_contract_compat`].
+#[derive(Clone, Debug, Default)]
+pub struct CompatReport {
+    /// Set when the module could not be decoded, e.g. it uses an unsupported proposal.
+    pub parse_error: Option<String>,
+    /// Post-MVP proposals the module uses.
+    pub proposals: Vec<WasmProposal>,
+    /// The used proposals the engine doesn't support, see [`WasmProposal::is_supported`].
+    pub unsupported_proposals: Vec<WasmProposal>,
+    /// `(module, name)` of every imported function, in import order.
+    pub host_imports: Vec<(String, String)>,
+    /// Imported functions no host function is registered for, as `module.name`.
+    pub missing_host_funcs: Vec<String>,
+    /// Imported functions registered with a different signature, as `module.name`.
+    pub mismatched_host_funcs: Vec<String>,
+    /// Whether the module uses floating point types or instructions.
+    pub uses_float: bool,
+    /// Why gas metering failed, if it did.
+    pub metering_error: Option<String>,
+    /// Actionable fixes for the problems above.
+    pub suggestions: Vec<String>,
+}
+
+impl CompatReport {
+    /// Whether the engine supports the module, it can be metered and all of its imports are
+    /// registered.
+    pub fn is_compatible(&self) -> bool {
+        self.parse_error.is_none()
+            && self.unsupported_proposals.is_empty()
+            && self.metering_error.is_none()
+            && self.missing_host_funcs.is_empty()
+            && self.mismatched_host_funcs.is_empty()
+    }
+}
+
+fn to_zen_value_type(value_type: &ValueType) -> Option<ZenValueType> {
+    match value_type {
+        ValueType::I32 => Some(ZenValueType::I32),
+        ValueType::I64 => Some(ZenValueType::I64),
+        ValueType::F32 => Some(ZenValueType::F32),
+        ValueType::F64 => Some(ZenValueType::F64),
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
+fn is_float_type(value_type: &ValueType) -> bool {
+    matches!(value_type, ValueType::F32 | ValueType::F64)
+}
+
+fn instruction_proposal(instruction: &Instruction) -> Option<WasmProposal> {
+    match instruction {
+        Instruction::SignExt(_) => Some(WasmProposal::SignExtension),
+        Instruction::Bulk(_) => Some(WasmProposal::BulkMemory),
+        Instruction::Simd(_) => Some(WasmProposal::Simd),
+        _ => None,
+    }
+}
+
+/// Analyze a contract against the metering layer and the `registered` host functions, see
+/// [`ZenRuntime::host_func_signatures`](super::runtime::ZenRuntime::host_func_signatures).
+pub fn analyze_contract_compat(bytes: &[u8], registered: &[ZenHostFuncSignature]) -> CompatReport {
+    let mut report = CompatReport::default();
+    let module = match elements::Module::from_bytes(bytes) {
+        Ok(module) => module,
+        Err(err) => {
+            report.parse_error = Some(err.to_string());
+            report.suggestions.push(format!(
+                "the module cannot be decoded ({err}): rebuild it for the wasm MVP plus \
+                 sign-extension, e.g. disable bulk-memory, simd, reference-types, tail-call and \
+                 threads in the compiler target features"
+            ));
+            return report;
+        }
+    };
+
+    let func_types: Vec<&elements::FunctionType> = module
+        .type_section()
+        .map(|section| {
+            section
+                .types()
+                .iter()
+                .map(|elements::Type::Function(func_type)| func_type)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut proposals = vec![];
+    if func_types
+        .iter()
+        .any(|func_type| func_type.results().len() > 1)
+    {
+        proposals.push(WasmProposal::MultiValue);
+    }
+    if let Some(import_section) = module.import_section() {
+        for import in import_section.entries() {
+            match import.external() {
+                elements::External::Function(type_idx) => {
+                    let name = format!("{}.{}", import.module(), import.field());
+                    report
+                        .host_imports
+                        .push((import.module().to_string(), import.field().to_string()));
+                    let registered = registered.iter().find(|signature| {
+                        signature.module == import.module() && signature.name == import.field()
+                    });
+                    let Some(registered) = registered else {
+                        report.suggestions.push(format!(
+                            "register a host function for {name} before loading the module"
+                        ));
+                        report.missing_host_funcs.push(name);
+                        continue;
+                    };
+                    let func_type = func_types.get(*type_idx as usize);
+                    let matches = func_type.is_some_and(|func_type| {
+                        let params = func_type.params().iter().map(to_zen_value_type);
+                        let results = func_type.results().iter().map(to_zen_value_type);
+                        params.eq(registered.params.iter().copied().map(Some))
+                            && results.eq(registered.results.iter().copied().map(Some))
+                    });
+                    if !matches {
+                        report.suggestions.push(format!(
+                            "the module imports {name} with a signature different from the \
+                             registered ({:?}) -> {:?}, align the host function with the contract \
+                             SDK",
+                            registered.params, registered.results
+                        ));
+                        report.mismatched_host_funcs.push(name);
+                    }
+                }
+                elements::External::Global(global_type) if global_type.is_mutable() => {
+                    proposals.push(WasmProposal::MutableGlobals);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    report.uses_float = func_types.iter().any(|func_type| {
+        func_type
+            .params()
+            .iter()
+            .chain(func_type.results())
+            .any(is_float_type)
+    });
+    if let Some(code_section) = module.code_section() {
+        for body in code_section.bodies() {
+            report.uses_float |= body
+                .locals()
+                .iter()
+                .any(|local| is_float_type(&local.value_type()));
+            for instruction in body.code().elements() {
+                proposals.extend(instruction_proposal(instruction));
+                report.uses_float |=
+                    instruction_class(instruction) == Some(InstructionClass::Float);
+            }
+        }
+    }
+    proposals.sort();
+    proposals.dedup();
+    report.unsupported_proposals = proposals
+        .iter()
+        .copied()
+        .filter(|proposal| !proposal.is_supported())
+        .collect();
+    report.proposals = proposals;
+    for proposal in &report.unsupported_proposals {
+        report.suggestions.push(format!(
+            "the module uses {proposal}, which the engine doesn't support: disable the \
+             {proposal} target feature of the compiler"
+        ));
+    }
+    if report.uses_float {
+        report.suggestions.push(
+            "the module uses floating point, which FloatPolicyPass rejects: build it with \
+             soft-float or integer-only code if the chain enforces deterministic execution"
+                .to_string(),
+        );
+    }
+
+    if let Err(err) = GasMeter::transform_default(bytes) {
+        report.suggestions.push(format!(
+            "gas metering failed ({err}): check the module for instructions the cost rules forbid"
+        ));
+        report.metering_error = Some(err.to_string());
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_host_number() -> ZenHostFuncSignature {
+        ZenHostFuncSignature {
+            module: "env".to_string(),
+            name: "get_host_number".to_string(),
+            params: vec![ZenValueType::I32, ZenValueType::I32],
+            results: vec![ZenValueType::I32],
+        }
+    }
+
+    #[test]
+    fn test_analyze_contract_compat() {
+        let wat = r#"
+            (module
+                (import "env" "get_host_number" (func $host (param i32 i32) (result i32)))
+                (import "env" "finish" (func $finish (param i32)))
+                (memory 1)
+                (func $test (param $a i32) (result i32)
+                    local.get $a
+                    i32.extend8_s
+                    i32.const 1
+                    call $host
+                    i32.const 0
+                    i32.const 0
+                    i32.const 16
+                    memory.copy
+                )
+                (export "test" (func $test))
+            )
+        "#;
+        let wasm = wat::parse_str(wat).expect("Failed to parse WAT");
+
+        let report = analyze_contract_compat(&wasm, &[get_host_number()]);
+        assert!(report.parse_error.is_none());
+        assert!(report.metering_error.is_none());
+        assert!(!report.uses_float);
+        assert_eq!(
+            vec![WasmProposal::SignExtension, WasmProposal::BulkMemory],
+            report.proposals
+        );
+        assert_eq!(2, report.host_imports.len());
+        assert_eq!(vec!["env.finish".to_string()], report.missing_host_funcs);
+        assert!(report.mismatched_host_funcs.is_empty());
+        assert_eq!(vec![WasmProposal::BulkMemory], report.unsupported_proposals);
+        assert!(!report.is_compatible());
+        assert!(report.suggestions[0].contains("env.finish"));
+        assert!(report.suggestions[1].contains("bulk-memory"));
+
+        // sign-extension alone is supported
+        let wat = r#"
+            (module
+                (func $test (param $a i32) (result i32)
+                    local.get $a
+                    i32.extend8_s
+                )
+                (export "test" (func $test))
+            )
+        "#;
+        let wasm = wat::parse_str(wat).expect("Failed to parse WAT");
+        let report = analyze_contract_compat(&wasm, &[]);
+        assert_eq!(vec![WasmProposal::SignExtension], report.proposals);
+        assert!(report.unsupported_proposals.is_empty());
+        assert!(report.is_compatible());
+    }
+
+    #[test]
+    fn test_analyze_contract_compat_signature_mismatch() {
+        let wat = r#"
+            (module
+                (import "env" "get_host_number" (func $host (param i64) (result i32)))
+            )
+        "#;
+        let wasm = wat::parse_str(wat).expect("Failed to parse WAT");
+        let report = analyze_contract_compat(&wasm, &[get_host_number()]);
+        assert_eq!(
+            vec!["env.get_host_number".to_string()],
+            report.mismatched_host_funcs
+        );
+        assert!(!report.is_compatible());
+
+        let report = analyze_contract_compat(b"invalid wasm bytes", &[]);
+        assert!(report.parse_error.is_some());
+        assert!(!report.is_compatible());
+        assert_eq!(1, report.suggestions.len());
+    }
+}
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 use super::r#extern::{ZenCreateRuntimeConfig, ZenDeleteRuntimeConfig, ZenRuntimeConfigExtern};
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ZenRuntimeMode {
     Interp,     // 0
     Singlepass, // 1
@@ -166,6 +166,14 @@ extern "C" {
         error_buf_size: cty::uint32_t,
     ) -> cty::int8_t;
 
+    // return bool
+    pub fn ZenGetInstanceErrorCode(
+        inst: *mut ZenInstanceExtern,
+        error_code: *mut cty::uint32_t,
+    ) -> cty::int8_t;
+
+    pub fn ZenClearInstanceError(inst: *mut ZenInstanceExtern);
+
     // return bool
     pub fn ZenValidateHostMemAddr(
         inst: *mut ZenInstanceExtern,
@@ -187,10 +195,14 @@ extern "C" {
         inst: *mut ZenInstanceExtern,
         host_addr: *const cty::c_void,
     ) -> cty::uint32_t;
+    pub fn ZenGetInstanceMemorySize(inst: *mut ZenInstanceExtern) -> cty::uint64_t;
 
     pub fn ZenGetInstanceGasLeft(inst: *mut ZenInstanceExtern) -> cty::uint64_t;
     pub fn ZenSetInstanceGasLeft(inst: *mut ZenInstanceExtern, new_gas: cty::uint64_t);
 
+    pub fn ZenInterruptInstance(inst: *mut ZenInstanceExtern);
+    pub fn ZenClearInstanceInterrupt(inst: *mut ZenInstanceExtern);
+
     pub fn ZenSetInstanceCustomData(inst: *mut ZenInstanceExtern, custom_data: *const cty::c_void);
     pub fn ZenGetInstanceCustomData(inst: *mut ZenInstanceExtern) -> *const cty::c_void;
 
@@ -202,6 +214,8 @@ extern "C" {
     pub fn ZenGetErrCodeEnvAbort() -> cty::uint32_t;
     pub fn ZenGetErrCodeGasLimitExceeded() -> cty::uint32_t;
     pub fn ZenGetErrCodeOutOfBoundsMemory() -> cty::uint32_t;
+    pub fn ZenGetErrCodeOutOfGas() -> cty::uint32_t;
+    pub fn ZenGetErrCodeInterrupted() -> cty::uint32_t;
 
     pub fn ZenInstanceExit(inst: *mut ZenInstanceExtern, exit_code: cty::int32_t);
 
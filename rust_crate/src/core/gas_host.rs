@@ -0,0 +1,56 @@
+// Copyright (C) 2021-2025 the DTVM authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Host functions connecting gas metering with the instance gas counter.
+//!
+//! Modules instrumented with a local gas function are charged by the engine itself. Modules
+//! instrumented with [`GasFunction::Imported`](crate::gas_metering::GasFunction::Imported) call
+//! a host function instead, [`gas_host_funcs`] provides it. Either way the remaining gas lives
+//! in the instance, and `get_gas_left` lets contracts read it.
+
+use super::host_module::{HostFunctionError, ZenHostFuncDesc};
+use super::instance::ZenInstance;
+use super::r#extern::ZenInstanceExtern;
+use super::types::ZenValueType;
+
+/// Name of the imported gas function provided by [`gas_host_funcs`].
+pub const GAS_HOST_FUNC_NAME: &str = "gas";
+/// Name of the remaining gas host function provided by [`gas_host_funcs`].
+pub const GAS_LEFT_HOST_FUNC_NAME: &str = "get_gas_left";
+
+/// Charge `amount` to the instance, trapping with out of gas when it has less left.
+extern "C" fn charge_gas<T: 'static>(wasm_inst: *mut ZenInstanceExtern, amount: i64) {
+    let inst: &ZenInstance<T> = ZenInstance::from_raw_pointer(wasm_inst);
+    let gas_left = inst.get_gas_left();
+    match gas_left.checked_sub(amount as u64) {
+        Some(new_gas_left) if amount >= 0 => inst.set_gas_left(new_gas_left),
+        _ => {
+            inst.set_gas_left(0);
+            HostFunctionError::OutOfGas.raise(inst);
+        }
+    }
+}
+
+extern "C" fn get_gas_left<T: 'static>(wasm_inst: *mut ZenInstanceExtern) -> i64 {
+    let inst: &ZenInstance<T> = ZenInstance::from_raw_pointer(wasm_inst);
+    inst.get_gas_left().min(i64::MAX as u64) as i64
+}
+
+/// The `gas(i64)` and `get_gas_left() -> i64` host functions, for instances with context type
+/// `T`. Register them in the module named by the imported gas function, e.g. `env`.
+pub fn gas_host_funcs<T: 'static>() -> Vec<ZenHostFuncDesc> {
+    vec![
+        ZenHostFuncDesc {
+            name: GAS_HOST_FUNC_NAME.to_string(),
+            arg_types: vec![ZenValueType::I64],
+            ret_types: vec![],
+            ptr: charge_gas::<T> as *const cty::c_void,
+        },
+        ZenHostFuncDesc {
+            name: GAS_LEFT_HOST_FUNC_NAME.to_string(),
+            arg_types: vec![],
+            ret_types: vec![ZenValueType::I64],
+            ptr: get_gas_left::<T> as *const cty::c_void,
+        },
+    ]
+}
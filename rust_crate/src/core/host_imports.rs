@@ -0,0 +1,206 @@
+// Copyright (C) 2021-2025 the DTVM authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Matching the host functions a module imports against the registered ones.
+//!
+//! Embedders usually register every host API they implement for every contract. Checking the
+//! import section first lets them build a host module holding only what a contract uses, see
+//! [`ZenRuntime::create_minimal_host_module`](super::runtime::ZenRuntime::create_minimal_host_module),
+//! and report registered capabilities a contract never touches for policy review.
+
+use super::abi_version::{read_leb_u32, WASM_MAGIC};
+use super::host_module::ZenHostFuncDesc;
+
+const IMPORT_SECTION_ID: u8 = 2;
+
+const IMPORT_KIND_FUNC: u8 = 0;
+const IMPORT_KIND_TABLE: u8 = 1;
+const IMPORT_KIND_MEMORY: u8 = 2;
+const IMPORT_KIND_GLOBAL: u8 = 3;
+const IMPORT_KIND_TAG: u8 = 4;
+
+fn read_byte(bytes: &[u8], pos: &mut usize) -> Result<u8, String> {
+    let byte = *bytes
+        .get(*pos)
+        .ok_or_else(|| "unexpected end of wasm import section".to_string())?;
+    *pos += 1;
+    Ok(byte)
+}
+
+fn read_name(bytes: &[u8], pos: &mut usize) -> Result<String, String> {
+    let len = read_leb_u32(bytes, pos)? as usize;
+    let end = pos
+        .checked_add(len)
+        .filter(|end| *end <= bytes.len())
+        .ok_or_else(|| "import name exceeds section size".to_string())?;
+    let name = std::str::from_utf8(&bytes[*pos..end])
+        .map_err(|_| "import name is not valid utf-8".to_string())?;
+    *pos = end;
+    Ok(name.to_string())
+}
+
+/// Skip a leb128 integer of any width, e.g. the 64-bit limits of `memory64`.
+fn skip_leb(bytes: &[u8], pos: &mut usize) -> Result<(), String> {
+    while read_byte(bytes, pos)? & 0x80 != 0 {}
+    Ok(())
+}
+
+fn skip_limits(bytes: &[u8], pos: &mut usize) -> Result<(), String> {
+    let flags = read_byte(bytes, pos)?;
+    skip_leb(bytes, pos)?;
+    if flags & 1 != 0 {
+        skip_leb(bytes, pos)?;
+    }
+    Ok(())
+}
+
+/// Read the `(module, name)` pairs of the functions imported by a wasm module, in import order.
+///
+/// Like [`read_host_abi_version`](super::abi_version::read_host_abi_version), only the sections
+/// needed are decoded, so this works regardless of which wasm proposals the code uses.
+pub fn read_imported_funcs(code: &[u8]) -> Result<Vec<(String, String)>, String> {
+    if code.len() < 8 || &code[0..4] != WASM_MAGIC {
+        return Err("invalid wasm magic".to_string());
+    }
+    let mut pos = 8;
+    while pos < code.len() {
+        let section_id = code[pos];
+        pos += 1;
+        let section_size = read_leb_u32(code, &mut pos)? as usize;
+        let section_end = pos
+            .checked_add(section_size)
+            .filter(|end| *end <= code.len())
+            .ok_or_else(|| "wasm section exceeds module size".to_string())?;
+        if section_id == IMPORT_SECTION_ID {
+            let section = &code[..section_end];
+            let count = read_leb_u32(section, &mut pos)?;
+            let mut funcs = vec![];
+            for _ in 0..count {
+                let module = read_name(section, &mut pos)?;
+                let name = read_name(section, &mut pos)?;
+                match read_byte(section, &mut pos)? {
+                    IMPORT_KIND_FUNC => {
+                        read_leb_u32(section, &mut pos)?;
+                        funcs.push((module, name));
+                    }
+                    IMPORT_KIND_TABLE => {
+                        read_byte(section, &mut pos)?;
+                        skip_limits(section, &mut pos)?;
+                    }
+                    IMPORT_KIND_MEMORY => skip_limits(section, &mut pos)?,
+                    IMPORT_KIND_GLOBAL => {
+                        read_byte(section, &mut pos)?;
+                        read_byte(section, &mut pos)?;
+                    }
+                    IMPORT_KIND_TAG => {
+                        read_byte(section, &mut pos)?;
+                        read_leb_u32(section, &mut pos)?;
+                    }
+                    kind => return Err(format!("unknown import kind {kind}")),
+                }
+            }
+            return Ok(funcs);
+        }
+        pos = section_end;
+    }
+    Ok(vec![])
+}
+
+/// Which registered host functions of one host module a wasm module uses.
+#[derive(Clone, Default)]
+pub struct HostImportUsage {
+    /// Registered host functions imported by the module, in registration order.
+    pub used: Vec<ZenHostFuncDesc>,
+    /// Registered host functions the module does not import.
+    pub unused: Vec<String>,
+    /// Functions the module imports from the host module that are not registered.
+    pub missing: Vec<String>,
+}
+
+/// Match the functions `code` imports from `host_module_name` against `host_func_descs`.
+pub fn host_import_usage<'a, T: Iterator<Item = &'a ZenHostFuncDesc>>(
+    code: &[u8],
+    host_module_name: &str,
+    host_func_descs: T,
+) -> Result<HostImportUsage, String> {
+    let imported: Vec<String> = read_imported_funcs(code)?
+        .into_iter()
+        .filter(|(module, _)| module == host_module_name)
+        .map(|(_, name)| name)
+        .collect();
+    let mut usage = HostImportUsage::default();
+    for desc in host_func_descs {
+        if imported.contains(&desc.name) {
+            usage.used.push(desc.clone());
+        } else {
+            usage.unused.push(desc.name.clone());
+        }
+    }
+    for name in imported {
+        if !usage.used.iter().any(|desc| desc.name == name) && !usage.missing.contains(&name) {
+            usage.missing.push(name);
+        }
+    }
+    Ok(usage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::ZenValueType;
+
+    fn host_func_desc(name: &str) -> ZenHostFuncDesc {
+        ZenHostFuncDesc {
+            name: name.to_string(),
+            arg_types: vec![ZenValueType::I32],
+            ret_types: vec![],
+            ptr: std::ptr::null(),
+        }
+    }
+
+    const WAT: &str = r#"
+        (module
+            (import "env" "memory" (memory 1 2))
+            (import "env" "table" (table 1 funcref))
+            (import "env" "counter" (global (mut i32)))
+            (import "env" "emit" (func (param i32)))
+            (import "other" "log" (func (param i32)))
+            (import "env" "finish" (func (param i32)))
+            (import "env" "emit" (func (param i32)))
+        )
+    "#;
+
+    #[test]
+    fn test_read_imported_funcs() {
+        let wasm = wat::parse_str(WAT).expect("Failed to parse WAT");
+        let funcs = read_imported_funcs(&wasm).unwrap();
+        let funcs: Vec<(&str, &str)> = funcs
+            .iter()
+            .map(|(module, name)| (module.as_str(), name.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("env", "emit"),
+                ("other", "log"),
+                ("env", "finish"),
+                ("env", "emit")
+            ],
+            funcs
+        );
+
+        let no_imports = wat::parse_str("(module)").expect("Failed to parse WAT");
+        assert!(read_imported_funcs(&no_imports).unwrap().is_empty());
+        assert!(read_imported_funcs(b"invalid wasm bytes").is_err());
+    }
+
+    #[test]
+    fn test_host_import_usage() {
+        let wasm = wat::parse_str(WAT).expect("Failed to parse WAT");
+        let descs = [host_func_desc("emit"), host_func_desc("revert")];
+        let usage = host_import_usage(&wasm, "env", descs.iter()).unwrap();
+        let used: Vec<&str> = usage.used.iter().map(|desc| desc.name.as_str()).collect();
+        assert_eq!(vec!["emit"], used);
+        assert_eq!(vec!["revert".to_string()], usage.unused);
+        assert_eq!(vec!["finish".to_string()], usage.missing);
+    }
+}
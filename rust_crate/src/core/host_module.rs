@@ -1,16 +1,23 @@
 // Copyright (C) 2021-2023 the DTVM authors. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 use super::{
+    instance::ZenInstance,
     r#extern::{
         ZenDeleteHostModule, ZenDeleteHostModuleDesc, ZenFilterHostFunctions,
-        ZenHostModuleDescExtern, ZenHostModuleExtern,
+        ZenGetErrCodeEnvAbort, ZenHostModuleDescExtern, ZenHostModuleExtern, ZenInstanceExtern,
+        ZenSetInstanceExceptionByHostapi,
     },
     types::ZenValueType,
     utils::{at_least, rust_str_to_c_str, ScopedMalloc},
 };
 use crate::core::runtime::ZenRuntime;
+use serde::Serialize;
+use std::any::{Any, TypeId};
 use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
 use std::rc::Rc;
+use std::time::Instant;
+use thiserror::Error;
 
 #[derive(Clone)]
 pub struct ZenHostFuncDesc {
@@ -25,6 +32,16 @@ pub struct ZenHostFuncDesc {
 unsafe impl Send for ZenHostFuncDesc {}
 unsafe impl Sync for ZenHostFuncDesc {}
 
+/// Signature of a registered host function, as listed by
+/// [`ZenRuntime::describe_host_functions`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ZenHostFuncSignature {
+    pub module: String,
+    pub name: String,
+    pub params: Vec<ZenValueType>,
+    pub results: Vec<ZenValueType>,
+}
+
 #[derive(Clone)]
 pub struct ZenHostModuleDesc {
     pub rt: RefCell<Option<Rc<ZenRuntime>>>,
@@ -92,3 +109,202 @@ impl ZenHostModule {
         success != 0
     }
 }
+
+/// Error returned by a closure host function, raised in the calling wasm instance.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum HostFunctionError {
+    #[error("out of gas")]
+    OutOfGas,
+    #[error("out of bounds memory access")]
+    OutOfBoundsMemory,
+    #[error("aborted by host function")]
+    Abort,
+    /// Exit the instance with the given code, see [`ZenInstance::exit`].
+    #[error("exit with code {0}")]
+    Exit(i32),
+    /// Raise an engine error code, see [`ZenInstance::set_exception_by_hostapi`].
+    #[error("host exception {0}")]
+    Exception(u32),
+    /// The host function panicked. Raised as an abort, the message is kept for
+    /// [`ZenInstance::take_host_panic`].
+    #[error("host function panicked: {0}")]
+    Panic(String),
+}
+
+impl HostFunctionError {
+    pub(crate) fn raise<T>(&self, inst: &ZenInstance<T>) {
+        match self {
+            HostFunctionError::OutOfGas => inst.raise_out_of_gas_error(),
+            HostFunctionError::OutOfBoundsMemory => inst.raise_out_of_bounds_memory_error(),
+            HostFunctionError::Abort => inst.raise_abort_error(),
+            HostFunctionError::Exit(code) => inst.exit(*code),
+            HostFunctionError::Exception(code) => inst.set_exception_by_hostapi(*code),
+            HostFunctionError::Panic(message) => {
+                inst.record_host_panic(message.clone());
+                inst.raise_abort_error();
+            }
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload
+            .downcast_ref::<&str>()
+            .map_or_else(|| "unknown panic payload".to_string(), |s| s.to_string()),
+    }
+}
+
+/// A Rust type passed to and from host functions as a wasm value.
+pub trait WasmType: Copy + Default + 'static {
+    const VALUE_TYPE: ZenValueType;
+}
+
+impl WasmType for i32 {
+    const VALUE_TYPE: ZenValueType = ZenValueType::I32;
+}
+
+impl WasmType for i64 {
+    const VALUE_TYPE: ZenValueType = ZenValueType::I64;
+}
+
+impl WasmType for f32 {
+    const VALUE_TYPE: ZenValueType = ZenValueType::F32;
+}
+
+impl WasmType for f64 {
+    const VALUE_TYPE: ZenValueType = ZenValueType::F64;
+}
+
+/// The result of a host function: `()` or a single [`WasmType`].
+pub trait WasmResults: Default + 'static {
+    fn value_types() -> Vec<ZenValueType>;
+}
+
+impl WasmResults for () {
+    fn value_types() -> Vec<ZenValueType> {
+        vec![]
+    }
+}
+
+impl<T: WasmType> WasmResults for T {
+    fn value_types() -> Vec<ZenValueType> {
+        vec![T::VALUE_TYPE]
+    }
+}
+
+/// A Rust closure usable as a host function of instances with context type `C`, see
+/// [`ZenRuntime::register_host_closure`].
+///
+/// Implemented for `Fn(&ZenInstance<C>, A1, .., An) -> Result<R, HostFunctionError>` with up
+/// to four [`WasmType`] arguments. `Args` only tells the arities apart.
+pub trait IntoHostFunc<C, Args>: 'static {
+    fn arg_types() -> Vec<ZenValueType>;
+    fn ret_types() -> Vec<ZenValueType>;
+    /// The `extern "C"` trampoline calling the closure registered in `slot` for this closure
+    /// type, `None` when `slot` is not below [`HOST_CLOSURE_SLOTS`].
+    fn trampoline(slot: usize) -> Option<*const cty::c_void>;
+}
+
+/// Number of closures of the same closure type a runtime can register, each needs its own
+/// trampoline.
+pub const HOST_CLOSURE_SLOTS: usize = 8;
+
+/// Look up the closure of type `F` registered in `slot` of the runtime of the calling instance
+/// and run `call` with it, raising its error in the instance.
+fn invoke_host_closure<C: 'static, F: 'static, R: Default>(
+    wasm_inst: *mut ZenInstanceExtern,
+    slot: usize,
+    call: impl FnOnce(&F, &ZenInstance<C>) -> Result<R, HostFunctionError>,
+) -> R {
+    // the closure only takes instances created with context type C
+    let Some(inst) = ZenInstance::<C>::try_from_raw_pointer(wasm_inst) else {
+        unsafe { ZenSetInstanceExceptionByHostapi(wasm_inst, ZenGetErrCodeEnvAbort()) };
+        return R::default();
+    };
+    // release the runtime borrow before running the closure, it may call back into wasm
+    let closure = inst.rt.try_borrow().ok().and_then(|rt| {
+        rt.as_ref()
+            .and_then(|rt| rt.host_closure(TypeId::of::<F>(), slot))
+    });
+    let Some((closure, f)) = closure.as_ref().and_then(|closure| {
+        let f = closure.func.downcast_ref::<F>()?;
+        Some((closure, f))
+    }) else {
+        inst.raise_abort_error();
+        return R::default();
+    };
+    // a panic must not unwind into the engine, it aborts the wasm call instead
+    let guarded_call = || {
+        panic::catch_unwind(AssertUnwindSafe(|| call(f, inst)))
+            .unwrap_or_else(|payload| Err(HostFunctionError::Panic(panic_message(payload))))
+    };
+    let started = inst.host_call_metrics_enabled().then(Instant::now);
+    #[cfg(feature = "otel")]
+    let result =
+        super::telemetry::in_host_span(inst, &closure.category, &closure.name, guarded_call);
+    #[cfg(not(feature = "otel"))]
+    let result = guarded_call();
+    if let Some(started) = started {
+        inst.record_host_call(&closure.name, started.elapsed());
+    }
+    match result {
+        Ok(result) => result,
+        Err(err) => {
+            err.raise(inst);
+            R::default()
+        }
+    }
+}
+
+macro_rules! impl_into_host_func {
+    ($trampoline:ident; $($arg:ident: $ty:ident),*) => {
+        extern "C" fn $trampoline<const SLOT: usize, C: 'static, F, R, $($ty),*>(
+            wasm_inst: *mut ZenInstanceExtern,
+            $($arg: $ty),*
+        ) -> R
+        where
+            F: Fn(&ZenInstance<C>, $($ty),*) -> Result<R, HostFunctionError> + 'static,
+            R: WasmResults,
+            $($ty: WasmType),*
+        {
+            invoke_host_closure::<C, F, R>(wasm_inst, SLOT, |f, inst| f(inst, $($arg),*))
+        }
+
+        impl<C: 'static, F, R, $($ty),*> IntoHostFunc<C, ($($ty,)*)> for F
+        where
+            F: Fn(&ZenInstance<C>, $($ty),*) -> Result<R, HostFunctionError> + 'static,
+            R: WasmResults,
+            $($ty: WasmType),*
+        {
+            fn arg_types() -> Vec<ZenValueType> {
+                vec![$($ty::VALUE_TYPE),*]
+            }
+
+            fn ret_types() -> Vec<ZenValueType> {
+                R::value_types()
+            }
+
+            fn trampoline(slot: usize) -> Option<*const cty::c_void> {
+                let trampolines: [*const cty::c_void; HOST_CLOSURE_SLOTS] = [
+                    $trampoline::<0, C, F, R, $($ty),*> as *const cty::c_void,
+                    $trampoline::<1, C, F, R, $($ty),*> as *const cty::c_void,
+                    $trampoline::<2, C, F, R, $($ty),*> as *const cty::c_void,
+                    $trampoline::<3, C, F, R, $($ty),*> as *const cty::c_void,
+                    $trampoline::<4, C, F, R, $($ty),*> as *const cty::c_void,
+                    $trampoline::<5, C, F, R, $($ty),*> as *const cty::c_void,
+                    $trampoline::<6, C, F, R, $($ty),*> as *const cty::c_void,
+                    $trampoline::<7, C, F, R, $($ty),*> as *const cty::c_void,
+                ];
+                trampolines.get(slot).copied()
+            }
+        }
+    };
+}
+
+impl_into_host_func!(host_closure_trampoline0;);
+impl_into_host_func!(host_closure_trampoline1; a1: A1);
+impl_into_host_func!(host_closure_trampoline2; a1: A1, a2: A2);
+impl_into_host_func!(host_closure_trampoline3; a1: A1, a2: A2, a3: A3);
+impl_into_host_func!(host_closure_trampoline4; a1: A1, a2: A2, a3: A3, a4: A4);
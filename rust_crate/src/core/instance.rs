@@ -2,21 +2,32 @@
 // SPDX-License-Identifier: Apache-2.0
 use std::ffi::CStr;
 
+use crate::core::config::ZenRuntimeMode;
 use crate::core::r#extern::{
-    ZenGetErrCodeEnvAbort, ZenGetErrCodeGasLimitExceeded, ZenGetErrCodeOutOfBoundsMemory,
-    ZenInstanceProtectMemoryAgain,
+    ZenGetErrCodeEnvAbort, ZenGetErrCodeGasLimitExceeded, ZenGetErrCodeInterrupted,
+    ZenGetErrCodeOutOfBoundsMemory, ZenGetErrCodeOutOfGas, ZenInstanceProtectMemoryAgain,
 };
 use crate::core::runtime::ZenRuntime;
 use cty::c_void;
-use std::cell::RefCell;
+use std::any::{Any, TypeId};
+use std::cell::{Cell, RefCell, RefMut};
+use std::collections::HashMap;
+#[cfg(feature = "failure-injection")]
+use std::ops::Range;
 use std::rc::Rc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use thiserror::Error;
 
 use super::{
     isolation::ZenIsolation,
+    metrics::HostCallMetrics,
     r#extern::{
-        ZenCallWasmFuncByName, ZenDeleteInstance, ZenGetAppMemOffset, ZenGetHostMemAddr,
-        ZenGetInstanceCustomData, ZenGetInstanceError, ZenGetInstanceGasLeft, ZenInstanceExit,
-        ZenInstanceExtern, ZenSetInstanceCustomData, ZenSetInstanceExceptionByHostapi,
+        ZenCallWasmFuncByName, ZenClearInstanceInterrupt, ZenDeleteInstance, ZenGetAppMemOffset,
+        ZenGetHostMemAddr, ZenGetInstanceCustomData, ZenGetInstanceError, ZenGetInstanceErrorCode,
+        ZenGetInstanceGasLeft, ZenGetInstanceMemorySize, ZenInstanceExit, ZenInstanceExtern,
+        ZenInterruptInstance, ZenSetInstanceCustomData, ZenSetInstanceExceptionByHostapi,
         ZenSetInstanceGasLeft, ZenValidateAppMemAddr, ZenValidateHostMemAddr, ZenValueExtern,
     },
     runtime::{ZenModule, ERROR_BUF_SIZE},
@@ -24,13 +35,85 @@ use super::{
     utils::{at_least, rust_str_to_c_str, ScopedMalloc},
 };
 
+/// Error returned when an instance value is already borrowed by an outer host function frame.
+#[derive(Error, Debug)]
+pub enum ZenBorrowError {
+    #[error("scratch value {0} is already borrowed")]
+    ScratchBusy(&'static str),
+}
+
+/// Error of [`ZenInstance::call_wasm_func_typed`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ZenCallError {
+    /// The gas limit was exhausted, by the injected gas function or a host function raising
+    /// [`HostFunctionError::OutOfGas`](super::host_module::HostFunctionError::OutOfGas).
+    #[error("out of gas")]
+    OutOfGas,
+    /// Any other trap, with the engine error code and the error message.
+    #[error("{message}")]
+    Trap { code: u32, message: String },
+    /// [`ZenInstance::call_wasm_func_with_timeout`] interrupted the call.
+    #[error("timeout")]
+    Timeout,
+    /// A closure host function panicked, with the panic message.
+    #[error("host function panicked: {0}")]
+    HostPanic(String),
+    /// The call failed without an engine error, e.g. the runtime is shut down.
+    #[error("{0}")]
+    Other(String),
+}
+
+impl ZenCallError {
+    /// Classify a call that failed with `message`, from the engine error code of the instance
+    /// (see [`ZenInstance::error_code`]), `None` when the call failed before reaching the engine.
+    pub fn from_error_code(code: Option<u32>, message: String) -> ZenCallError {
+        match code {
+            Some(code) if code == unsafe { ZenGetErrCodeOutOfGas() } => ZenCallError::OutOfGas,
+            Some(code) if code == unsafe { ZenGetErrCodeInterrupted() } => ZenCallError::Timeout,
+            Some(code) => ZenCallError::Trap { code, message },
+            None => ZenCallError::Other(message),
+        }
+    }
+}
+
+/// A wasm instance and the host-side state attached to it.
+///
+/// # Reentrancy
+///
+/// Host functions get `&ZenInstance` from the raw instance pointer while wasm is running, and a
+/// host function may call back into wasm (`call_wasm_func`), which may invoke further host
+/// functions. Panicking inside a hand-written `extern "C"` host function aborts the whole
+/// process (closure host functions catch panics, see [`ZenInstance::take_host_panic`]), so the
+/// interior mutability of the instance follows two rules:
+///
+/// - the instance never holds a `RefCell` borrow across a call into the engine;
+/// - borrows handed out to host functions have a non-panicking `try_*` form returning
+///   [`ZenBorrowError`], which host functions that may be nested should prefer.
+///
+/// The same rules are recommended for `RefCell`s stored in the user `extra_ctx`: take the value
+/// out (or copy it) before calling back into wasm and put it back afterwards.
+#[repr(C)]
 pub struct ZenInstance<T> {
+    // TypeId of T, first so it can be read before knowing T, see try_from_raw_pointer
+    context_type: TypeId,
     pub rt: RefCell<Option<Rc<ZenRuntime>>>,
     pub isolation: RefCell<Option<Rc<ZenIsolation>>>,
     pub wasm_mod: RefCell<Option<Rc<ZenModule>>>,
     pub ptr: *mut ZenInstanceExtern,
     // extra ctx data
     pub extra_ctx: T,
+    // typed scratch state shared by host functions, cleared when the top-level call returns
+    scratch: RefCell<HashMap<TypeId, Box<dyn Any>>>,
+    call_depth: Cell<u32>,
+    // gas the instance was created or last reset with, see set_gas_limit
+    gas_limit: Cell<u64>,
+    // message of the last host function panic during the current top-level call
+    host_panic: RefCell<Option<String>>,
+    // closure host function statistics, None until enabled
+    host_call_metrics: RefCell<Option<HostCallMetrics>>,
+    // wasm address ranges validate_wasm_addr reports as invalid
+    #[cfg(feature = "failure-injection")]
+    failing_wasm_addrs: RefCell<Vec<Range<u32>>>,
 }
 
 impl<T> Drop for ZenInstance<T> {
@@ -40,6 +123,9 @@ impl<T> Drop for ZenInstance<T> {
                 ZenDeleteInstance(self.isolation.borrow().as_ref().unwrap().ptr, self.ptr);
             }
         }
+        if let Some(rt) = self.rt.borrow().as_ref() {
+            rt.update_live_resources(|live| live.instances -= 1);
+        }
         // remove ref of isolation, so the isolation can be auto-dropped after instance dropped
         *self.isolation.borrow_mut() = None;
         // remove ref of ZenModule
@@ -70,6 +156,40 @@ impl<T> ZenInstance<T> {
         &self.extra_ctx
     }
 
+    /// Get the per-instance scratch value of type `S`, creating it with `S::default()` first.
+    ///
+    /// Scratch values let a sequence of host function calls share state (e.g. the salt of an
+    /// in-progress create) without globals. They live until the outermost `call_wasm_func`
+    /// on this instance returns.
+    ///
+    /// Panics if the scratch area is already borrowed, see [`ZenInstance::try_scratch`].
+    pub fn scratch<S: Any + Default>(&self) -> RefMut<'_, S> {
+        self.try_scratch::<S>().unwrap()
+    }
+
+    /// Like [`ZenInstance::scratch`], but returns an error instead of panicking when an outer
+    /// host function frame still holds a scratch borrow.
+    pub fn try_scratch<S: Any + Default>(&self) -> Result<RefMut<'_, S>, ZenBorrowError> {
+        let scratch = self
+            .scratch
+            .try_borrow_mut()
+            .map_err(|_| ZenBorrowError::ScratchBusy(std::any::type_name::<S>()))?;
+        Ok(RefMut::map(scratch, |scratch| {
+            scratch
+                .entry(TypeId::of::<S>())
+                .or_insert_with(|| Box::new(S::default()))
+                .downcast_mut::<S>()
+                .unwrap()
+        }))
+    }
+
+    /// Drop all scratch values of this instance. Does nothing while a scratch value is borrowed.
+    pub fn clear_scratch(&self) {
+        if let Ok(mut scratch) = self.scratch.try_borrow_mut() {
+            scratch.clear();
+        }
+    }
+
     /// get rust ZenInstance from c instance* pointer
     #[allow(clippy::not_unsafe_ptr_arg_deref)]
     pub fn from_raw_pointer(c_ptr: *mut ZenInstanceExtern) -> &'static ZenInstance<T> {
@@ -78,6 +198,23 @@ impl<T> ZenInstance<T> {
         unsafe { &*rust_ptr }
     }
 
+    /// Like [`ZenInstance::from_raw_pointer`], `None` when the instance was not created with
+    /// context type `T`.
+    pub(crate) fn try_from_raw_pointer(
+        c_ptr: *mut ZenInstanceExtern,
+    ) -> Option<&'static ZenInstance<T>>
+    where
+        T: 'static,
+    {
+        let rust_ptr = unsafe { ZenGetInstanceCustomData(c_ptr) as *const ZenInstance<T> };
+        if rust_ptr.is_null() {
+            return None;
+        }
+        // context_type is at offset 0 whatever the context type of the instance is
+        let context_type = unsafe { *(rust_ptr as *const TypeId) };
+        (context_type == TypeId::of::<T>()).then(|| unsafe { &*rust_ptr })
+    }
+
     /// get host memory from linear memory offset
     pub fn get_host_memory(&self, offset: u32) -> *mut u8 {
         let ptr = unsafe { ZenGetHostMemAddr(self.ptr, offset) };
@@ -89,10 +226,38 @@ impl<T> ZenInstance<T> {
     }
 
     pub fn validate_wasm_addr(&self, offset: u32, size: u32) -> bool {
+        #[cfg(feature = "failure-injection")]
+        if self.is_failing_wasm_addr(offset, size) {
+            return false;
+        }
         let bool_int = unsafe { ZenValidateAppMemAddr(self.ptr, offset, size) };
         bool_int != 0
     }
 
+    /// Make [`ZenInstance::validate_wasm_addr`] fail for every access overlapping `range`, so
+    /// embedders can exercise the error paths of their host functions without crafting
+    /// malicious wasm.
+    #[cfg(feature = "failure-injection")]
+    pub fn fail_wasm_addr_validation(&self, range: Range<u32>) {
+        self.failing_wasm_addrs.borrow_mut().push(range);
+    }
+
+    /// Remove all ranges added by [`ZenInstance::fail_wasm_addr_validation`].
+    #[cfg(feature = "failure-injection")]
+    pub fn clear_wasm_addr_failures(&self) {
+        self.failing_wasm_addrs.borrow_mut().clear();
+    }
+
+    #[cfg(feature = "failure-injection")]
+    fn is_failing_wasm_addr(&self, offset: u32, size: u32) -> bool {
+        let end = offset.saturating_add(size.max(1));
+        self.failing_wasm_addrs.try_borrow().is_ok_and(|ranges| {
+            ranges
+                .iter()
+                .any(|range| offset < range.end && range.start < end)
+        })
+    }
+
     pub fn validate_host_addr(&self, host_addr: *const u8, size: u32) -> bool {
         let bool_int =
             unsafe { ZenValidateHostMemAddr(self.ptr, host_addr as *const cty::c_void, size) };
@@ -109,6 +274,30 @@ impl<T> ZenInstance<T> {
         }
     }
 
+    /// The gas the instance was created with, or last given by [`ZenInstance::set_gas_limit`].
+    pub fn gas_limit(&self) -> u64 {
+        self.gas_limit.get()
+    }
+
+    /// Start a new gas budget: set both the gas limit and the gas left to `gas_limit`.
+    pub fn set_gas_limit(&self, gas_limit: u64) {
+        self.gas_limit.set(gas_limit);
+        self.set_gas_left(gas_limit);
+    }
+
+    /// Size in bytes of the linear memory, 0 when the module has none.
+    pub fn memory_size(&self) -> u64 {
+        unsafe { ZenGetInstanceMemorySize(self.ptr) }
+    }
+
+    /// The engine error code of the last failed call, as it appears in the error message of
+    /// [`ZenInstance::call_wasm_func`]. `None` when the instance has no error.
+    pub fn error_code(&self) -> Option<u32> {
+        let mut error_code: u32 = 0;
+        let has_error = unsafe { ZenGetInstanceErrorCode(self.ptr, &mut error_code) };
+        (has_error != 0).then_some(error_code)
+    }
+
     pub fn raise_out_of_gas_error(&self) {
         let err_code = unsafe { ZenGetErrCodeGasLimitExceeded() };
         self.set_exception_by_hostapi(err_code)
@@ -144,19 +333,34 @@ impl<T> ZenInstance<T> {
 
     /// because &ZenInstance will be get in hostapi by pointer cast
     /// so the memory of the ZenInstance should not be moved
+    ///
+    /// `gas_limit` is the gas `ptr` was created with.
     pub fn new(
         rt: Rc<ZenRuntime>,
         isolation: Rc<ZenIsolation>,
         wasm_mod: &Rc<ZenModule>,
         ptr: *mut ZenInstanceExtern,
+        gas_limit: u64,
         extra_ctx: T,
-    ) -> Rc<ZenInstance<T>> {
+    ) -> Rc<ZenInstance<T>>
+    where
+        T: 'static,
+    {
+        rt.update_live_resources(|live| live.instances += 1);
         let inst = Rc::new(ZenInstance {
+            context_type: TypeId::of::<T>(),
             rt: RefCell::new(Some(rt)),
             isolation: RefCell::new(Some(isolation)),
             wasm_mod: RefCell::new(Some(wasm_mod.clone())),
             ptr,
             extra_ctx,
+            scratch: RefCell::new(HashMap::new()),
+            call_depth: Cell::new(0),
+            gas_limit: Cell::new(gas_limit),
+            host_panic: RefCell::new(None),
+            host_call_metrics: RefCell::new(None),
+            #[cfg(feature = "failure-injection")]
+            failing_wasm_addrs: RefCell::new(vec![]),
         });
         inst.set_raw_custom_data(inst.as_ref() as *const ZenInstance<T>);
         inst
@@ -171,6 +375,149 @@ impl<T> ZenInstance<T> {
         &self,
         func_name: &str,
         args: &[ZenValue],
+    ) -> Result<Vec<ZenValue>, String> {
+        self.rt.borrow().as_ref().unwrap().check_running()?;
+        // host functions may call back into the instance, only the outermost call ends the frame
+        if self.call_depth.get() == 0 {
+            self.host_panic.borrow_mut().take();
+        }
+        self.call_depth.set(self.call_depth.get() + 1);
+        #[cfg(feature = "otel")]
+        let result = super::telemetry::in_call_span(self, func_name, args, || {
+            self.call_wasm_func_inner(func_name, args)
+        });
+        #[cfg(not(feature = "otel"))]
+        let result = self.call_wasm_func_inner(func_name, args);
+        self.call_depth.set(self.call_depth.get() - 1);
+        if self.call_depth.get() == 0 {
+            self.clear_scratch();
+        }
+        result
+    }
+
+    /// Like [`ZenInstance::call_wasm_func`], but tells out of gas apart from other traps, e.g.
+    /// to distinguish it from a revert.
+    pub fn call_wasm_func_typed(
+        &self,
+        func_name: &str,
+        args: &[ZenValue],
+    ) -> Result<Vec<ZenValue>, ZenCallError> {
+        self.call_wasm_func(func_name, args)
+            .map_err(|err| match self.take_host_panic() {
+                Some(message) => ZenCallError::HostPanic(message),
+                None => ZenCallError::from_error_code(self.error_code(), err),
+            })
+    }
+
+    /// Like [`ZenInstance::call_wasm_func_typed`], but interrupts the call with
+    /// [`ZenCallError::Timeout`] once it has run for `timeout`.
+    ///
+    /// A watchdog thread asks the engine to interrupt the call, which the interpreter checks at
+    /// every gas charge. The JIT modes never check it, so this fails right away unless the
+    /// runtime runs in [`ZenRuntimeMode::Interp`], and it only stops modules instrumented with
+    /// gas metering. It is not a wall-clock bound on host functions either: a host function
+    /// that blocks keeps the call running until it returns.
+    pub fn call_wasm_func_with_timeout(
+        &self,
+        func_name: &str,
+        args: &[ZenValue],
+        timeout: Duration,
+    ) -> Result<Vec<ZenValue>, ZenCallError> {
+        struct WatchedInstance(*mut ZenInstanceExtern);
+        // the watchdog only sets the atomic interrupt request of the instance, and is joined
+        // before the instance can be dropped
+        unsafe impl Send for WatchedInstance {}
+
+        let mode = self.rt.borrow().as_ref().unwrap().mode();
+        if mode != ZenRuntimeMode::Interp {
+            return Err(ZenCallError::Other(format!(
+                "call timeouts need the interpreter, the runtime runs in {mode:?} mode"
+            )));
+        }
+        unsafe { ZenClearInstanceInterrupt(self.ptr) };
+        let watched = WatchedInstance(self.ptr);
+        let (done, call_done) = mpsc::channel::<()>();
+        let watchdog = thread::spawn(move || {
+            let watched = watched;
+            if let Err(RecvTimeoutError::Timeout) = call_done.recv_timeout(timeout) {
+                unsafe { ZenInterruptInstance(watched.0) };
+            }
+        });
+        let result = self.call_wasm_func_typed(func_name, args);
+        drop(done);
+        watchdog.join().unwrap();
+        unsafe { ZenClearInstanceInterrupt(self.ptr) };
+        result
+    }
+
+    pub(crate) fn record_host_panic(&self, message: String) {
+        if let Ok(mut host_panic) = self.host_panic.try_borrow_mut() {
+            *host_panic = Some(message);
+        }
+    }
+
+    /// Start counting the calls and wall time of closure host functions on this instance,
+    /// see [`ZenInstance::host_call_metrics`]. Resets the statistics collected so far.
+    pub fn enable_host_call_metrics(&self) {
+        *self.host_call_metrics.borrow_mut() = Some(HostCallMetrics::default());
+    }
+
+    /// Stop collecting closure host function statistics and return them.
+    pub fn disable_host_call_metrics(&self) -> Option<HostCallMetrics> {
+        self.host_call_metrics.borrow_mut().take()
+    }
+
+    /// The closure host function statistics collected so far, `None` when not enabled.
+    pub fn host_call_metrics(&self) -> Option<HostCallMetrics> {
+        self.host_call_metrics.borrow().clone()
+    }
+
+    pub(crate) fn host_call_metrics_enabled(&self) -> bool {
+        self.host_call_metrics
+            .try_borrow()
+            .is_ok_and(|metrics| metrics.is_some())
+    }
+
+    pub(crate) fn record_host_call(&self, name: &str, elapsed: Duration) {
+        if let Ok(mut metrics) = self.host_call_metrics.try_borrow_mut() {
+            if let Some(metrics) = metrics.as_mut() {
+                metrics.record(name, elapsed);
+            }
+        }
+    }
+
+    /// Measure the body of a hand-written `extern "C"` host function `name` like a closure host
+    /// function, when host call metrics are enabled.
+    pub fn measure_host_call<R>(&self, name: &str, call: impl FnOnce() -> R) -> R {
+        let started = self.host_call_metrics_enabled().then(Instant::now);
+        let result = call();
+        if let Some(started) = started {
+            self.record_host_call(name, started.elapsed());
+        }
+        result
+    }
+
+    /// Take the message of a closure host function panic during the last top-level call. The
+    /// panic was turned into an abort of that call instead of unwinding into the engine.
+    pub fn take_host_panic(&self) -> Option<String> {
+        self.host_panic.borrow_mut().take()
+    }
+
+    /// Forget the Rust side state a previous user left on the instance: scratch values, host
+    /// panic, host call metrics, injected failures and a pending interrupt request.
+    pub(crate) fn reset_host_state(&self) {
+        self.clear_scratch();
+        self.host_panic.borrow_mut().take();
+        self.host_call_metrics.borrow_mut().take();
+        #[cfg(feature = "failure-injection")]
+        self.clear_wasm_addr_failures();
+        unsafe { ZenClearInstanceInterrupt(self.ptr) };
+    }
+
+    fn call_wasm_func_inner(
+        &self,
+        func_name: &str,
+        args: &[ZenValue],
     ) -> Result<Vec<ZenValue>, String> {
         let func_name_c_bytes = rust_str_to_c_str(func_name);
         let func_name_c_str = CStr::from_bytes_until_nul(&func_name_c_bytes).unwrap();
@@ -205,9 +552,11 @@ impl<T> ZenInstance<T> {
 
         let mut out_num_values: cty::uint32_t = 0;
 
+        // don't keep the runtime borrowed while wasm (and nested host functions) run
+        let rt_ptr = self.rt.borrow().as_ref().unwrap().ptr;
         let ret_bool = unsafe {
             ZenCallWasmFuncByName(
-                self.rt.borrow().as_ref().unwrap().ptr,
+                rt_ptr,
                 self.ptr,
                 func_name_c_str.as_ptr(),
                 func_c_args,
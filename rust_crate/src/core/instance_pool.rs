@@ -0,0 +1,197 @@
+// Copyright (C) 2021-2025 the DTVM authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Cache of compiled modules and idle instances, for loops running many short executions.
+//!
+//! Loading a module compiles it, and every instance needs its own isolation. An
+//! [`InstancePool`] keeps the modules it loaded by name, and instances released by a
+//! [`PooledInstance`] together with their isolation, so the next [`InstancePool::acquire`] of
+//! the same module only resets the gas, the context and whatever the reset hooks restore.
+//!
+//! The pool is only meant for stateless modules. A recycled instance keeps its linear memory
+//! and its mutable globals as the previous call left them, and the engine has no way to reset
+//! globals, so a module must not depend on either being in its initial state when a call
+//! starts. Reset hooks can rewrite memory, not globals. Instances whose last call trapped are
+//! dropped on release instead, since the trap may have left their memory half written and the
+//! engine error is still set.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::rc::{Rc, Weak};
+
+use super::instance::ZenInstance;
+use super::runtime::{ZenModule, ZenRuntime};
+
+type ResetHook<T> = Box<dyn Fn(&ZenInstance<T>) -> Result<(), String>>;
+
+/// A loaded module with the code it was compiled from.
+struct CachedModule {
+    wasm_mod: Rc<ZenModule>,
+    wasm_bytes: Box<[u8]>,
+}
+
+/// A pool of instances with context type `T`, see the [module documentation](self).
+pub struct InstancePool<T> {
+    rt: Rc<ZenRuntime>,
+    modules: RefCell<HashMap<String, CachedModule>>,
+    idle: RefCell<HashMap<String, Vec<Rc<ZenInstance<T>>>>>,
+    max_idle_per_module: usize,
+    reset_hooks: RefCell<Vec<ResetHook<T>>>,
+}
+
+impl<T: Clone + 'static> InstancePool<T> {
+    /// Create a pool keeping at most `max_idle_per_module` idle instances of each module.
+    pub fn new(rt: Rc<ZenRuntime>, max_idle_per_module: usize) -> Rc<InstancePool<T>> {
+        Rc::new(InstancePool {
+            rt,
+            modules: RefCell::new(HashMap::new()),
+            idle: RefCell::new(HashMap::new()),
+            max_idle_per_module,
+            reset_hooks: RefCell::new(vec![]),
+        })
+    }
+
+    /// Run `hook` on every recycled instance before it is handed out again, e.g. to rewrite
+    /// the memory regions a contract expects zeroed. Globals can't be restored, see the
+    /// [module documentation](self). An error drops the instance and a fresh one is created
+    /// instead.
+    pub fn add_reset_hook(&self, hook: impl Fn(&ZenInstance<T>) -> Result<(), String> + 'static) {
+        self.reset_hooks.borrow_mut().push(Box::new(hook));
+    }
+
+    /// Load and compile `wasm_bytes` as module `name`, unless it is already cached. Fails when
+    /// `name` is cached with different code, evict it first to replace it.
+    pub fn load_module(&self, name: &str, wasm_bytes: &[u8]) -> Result<Rc<ZenModule>, String> {
+        if let Some(cached) = self.modules.borrow().get(name) {
+            if *cached.wasm_bytes != *wasm_bytes {
+                return Err(format!(
+                    "module {name} is already loaded in the instance pool with different code"
+                ));
+            }
+            return Ok(cached.wasm_mod.clone());
+        }
+        let wasm_mod = self.rt.load_module_from_bytes(name, wasm_bytes)?;
+        self.modules.borrow_mut().insert(
+            name.to_string(),
+            CachedModule {
+                wasm_mod: wasm_mod.clone(),
+                wasm_bytes: wasm_bytes.into(),
+            },
+        );
+        Ok(wasm_mod)
+    }
+
+    /// The cached module `name`.
+    pub fn module(&self, name: &str) -> Option<Rc<ZenModule>> {
+        self.modules
+            .borrow()
+            .get(name)
+            .map(|cached| cached.wasm_mod.clone())
+    }
+
+    /// Drop module `name` and its idle instances, e.g. after the contract code changed.
+    pub fn evict_module(&self, name: &str) {
+        self.idle.borrow_mut().remove(name);
+        self.modules.borrow_mut().remove(name);
+    }
+
+    /// Number of idle instances of module `name`.
+    pub fn idle_count(&self, name: &str) -> usize {
+        self.idle.borrow().get(name).map_or(0, |idle| idle.len())
+    }
+
+    /// Get an instance of the cached module `name` with `gas_limit` gas and context `ctx`,
+    /// recycling an idle one when possible.
+    pub fn acquire(
+        self: &Rc<Self>,
+        name: &str,
+        gas_limit: u64,
+        ctx: T,
+    ) -> Result<PooledInstance<T>, String> {
+        let wasm_mod = self
+            .module(name)
+            .ok_or_else(|| format!("module {name} is not loaded in the instance pool"))?;
+        let inst = match self.recycle(name, ctx.clone()) {
+            Some(inst) => {
+                inst.set_gas_limit(gas_limit);
+                inst
+            }
+            None => {
+                let isolation = self.rt.new_isolation()?;
+                wasm_mod.new_instance_with_context(isolation, gas_limit, ctx)?
+            }
+        };
+        Ok(PooledInstance {
+            inst: Some(inst),
+            module: name.to_string(),
+            pool: Rc::downgrade(self),
+        })
+    }
+
+    /// Pop an idle instance of `name` that passes the reset hooks.
+    fn recycle(&self, name: &str, ctx: T) -> Option<Rc<ZenInstance<T>>> {
+        loop {
+            let mut inst = self.idle.borrow_mut().get_mut(name)?.pop()?;
+            let Some(inst_mut) = Rc::get_mut(&mut inst) else {
+                continue;
+            };
+            inst_mut.set_extra_ctx(ctx.clone());
+            inst.reset_host_state();
+            let reset = self
+                .reset_hooks
+                .borrow()
+                .iter()
+                .try_for_each(|hook| hook(&inst));
+            if reset.is_ok() {
+                return Some(inst);
+            }
+        }
+    }
+
+    fn release(&self, name: &str, inst: Rc<ZenInstance<T>>) {
+        // instances of evicted modules, instances still referenced elsewhere and instances
+        // whose last call trapped are dropped
+        if !self.modules.borrow().contains_key(name)
+            || Rc::strong_count(&inst) > 1
+            || inst.error_code().is_some()
+        {
+            return;
+        }
+        let mut idle = self.idle.borrow_mut();
+        let idle = idle.entry(name.to_string()).or_default();
+        if idle.len() < self.max_idle_per_module {
+            idle.push(inst);
+        }
+    }
+}
+
+/// An instance borrowed from an [`InstancePool`], returned to it when dropped.
+pub struct PooledInstance<T: Clone + 'static> {
+    inst: Option<Rc<ZenInstance<T>>>,
+    module: String,
+    pool: Weak<InstancePool<T>>,
+}
+
+impl<T: Clone + 'static> PooledInstance<T> {
+    /// Take the instance out of the pool for good.
+    pub fn detach(mut self) -> Rc<ZenInstance<T>> {
+        self.inst.take().unwrap()
+    }
+}
+
+impl<T: Clone + 'static> Deref for PooledInstance<T> {
+    type Target = ZenInstance<T>;
+
+    fn deref(&self) -> &ZenInstance<T> {
+        self.inst.as_ref().unwrap()
+    }
+}
+
+impl<T: Clone + 'static> Drop for PooledInstance<T> {
+    fn drop(&mut self) {
+        if let (Some(inst), Some(pool)) = (self.inst.take(), self.pool.upgrade()) {
+            pool.release(&self.module, inst);
+        }
+    }
+}
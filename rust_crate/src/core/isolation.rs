@@ -17,6 +17,9 @@ impl Drop for ZenIsolation {
                 ZenDeleteIsolation(self.rt.borrow().as_ref().unwrap().ptr, self.ptr);
             }
         }
+        if let Some(rt) = self.rt.borrow().as_ref() {
+            rt.update_live_resources(|live| live.isolations -= 1);
+        }
         // remove ref of ZenRuntime
         *self.rt.borrow_mut() = None;
     }
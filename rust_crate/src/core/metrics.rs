@@ -0,0 +1,160 @@
+// Copyright (C) 2021-2025 the DTVM authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Call counts and wall time of closure host functions, collected per instance once
+//! [`ZenInstance::enable_host_call_metrics`](super::instance::ZenInstance::enable_host_call_metrics)
+//! is called.
+//!
+//! The time of a host function includes the wasm it calls back into, so nested calls are
+//! counted in their caller as well.
+//!
+//! `extern "C"` host functions are called by the engine directly and are not measured unless
+//! they wrap their body in
+//! [`ZenInstance::measure_host_call`](super::instance::ZenInstance::measure_host_call). The gas
+//! host functions and the shared buffer extension are not wrapped.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Statistics of one host function.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HostCallStats {
+    pub calls: u64,
+    pub total_time: Duration,
+    pub max_time: Duration,
+}
+
+impl HostCallStats {
+    pub fn mean_time(&self) -> Duration {
+        if self.calls == 0 {
+            return Duration::ZERO;
+        }
+        self.total_time / u32::try_from(self.calls).unwrap_or(u32::MAX)
+    }
+}
+
+/// Statistics of every host function called so far, by host function name.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HostCallMetrics {
+    stats: BTreeMap<String, HostCallStats>,
+}
+
+impl HostCallMetrics {
+    pub(crate) fn record(&mut self, name: &str, elapsed: Duration) {
+        let stats = self.stats.entry(name.to_string()).or_default();
+        stats.calls += 1;
+        stats.total_time += elapsed;
+        stats.max_time = stats.max_time.max(elapsed);
+    }
+
+    /// Statistics of host function `name`.
+    pub fn get(&self, name: &str) -> Option<&HostCallStats> {
+        self.stats.get(name)
+    }
+
+    /// Statistics of all called host functions, by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &HostCallStats)> {
+        self.stats
+            .iter()
+            .map(|(name, stats)| (name.as_str(), stats))
+    }
+
+    /// A table of the host functions, most total time first.
+    pub fn report(&self) -> String {
+        let mut stats: Vec<_> = self.iter().collect();
+        stats.sort_by(|a, b| b.1.total_time.cmp(&a.1.total_time).then(a.0.cmp(b.0)));
+        let mut report = format!(
+            "{:<32} {:>10} {:>14} {:>14} {:>14}\n",
+            "host function", "calls", "total", "mean", "max"
+        );
+        for (name, stats) in stats {
+            let _ = writeln!(
+                report,
+                "{:<32} {:>10} {:>14} {:>14} {:>14}",
+                name,
+                stats.calls,
+                format!("{:?}", stats.total_time),
+                format!("{:?}", stats.mean_time()),
+                format!("{:?}", stats.max_time)
+            );
+        }
+        report
+    }
+
+    /// The statistics in the Prometheus text exposition format.
+    pub fn prometheus_text(&self) -> String {
+        let labels: Vec<(String, &HostCallStats)> = self
+            .iter()
+            .map(|(name, stats)| (escape_label_value(name), stats))
+            .collect();
+        let mut text = String::new();
+        text.push_str("# HELP dtvm_host_calls_total Number of host function calls.\n");
+        text.push_str("# TYPE dtvm_host_calls_total counter\n");
+        for (name, stats) in &labels {
+            let _ = writeln!(
+                text,
+                "dtvm_host_calls_total{{function=\"{name}\"}} {}",
+                stats.calls
+            );
+        }
+        text.push_str("# HELP dtvm_host_call_seconds_total Wall time spent in host functions.\n");
+        text.push_str("# TYPE dtvm_host_call_seconds_total counter\n");
+        for (name, stats) in &labels {
+            let _ = writeln!(
+                text,
+                "dtvm_host_call_seconds_total{{function=\"{name}\"}} {}",
+                stats.total_time.as_secs_f64()
+            );
+        }
+        text
+    }
+}
+
+/// Escape a Prometheus label value: backslash, double quote and line feed.
+fn escape_label_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_call_metrics() {
+        let mut metrics = HostCallMetrics::default();
+        metrics.record("storage_load", Duration::from_micros(30));
+        metrics.record("storage_load", Duration::from_micros(10));
+        metrics.record("get_caller", Duration::from_micros(1));
+
+        let stats = metrics.get("storage_load").unwrap();
+        assert_eq!(2, stats.calls);
+        assert_eq!(Duration::from_micros(40), stats.total_time);
+        assert_eq!(Duration::from_micros(20), stats.mean_time());
+        assert_eq!(Duration::from_micros(30), stats.max_time);
+        assert!(metrics.get("finish").is_none());
+
+        let report = metrics.report();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(3, lines.len());
+        assert!(lines[1].starts_with("storage_load"));
+        assert!(lines[2].starts_with("get_caller"));
+
+        let text = metrics.prometheus_text();
+        assert!(text.contains("dtvm_host_calls_total{function=\"storage_load\"} 2\n"));
+        assert!(text.contains("dtvm_host_call_seconds_total{function=\"get_caller\"} 0.000001\n"));
+
+        metrics.record("odd\"name\\\n", Duration::from_micros(1));
+        let text = metrics.prometheus_text();
+        assert!(text.contains("dtvm_host_calls_total{function=\"odd\\\"name\\\\\\n\"} 1\n"));
+    }
+}
@@ -1,11 +1,23 @@
 // Copyright (C) 2021-2023 the DTVM authors. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod abi_version;
+#[cfg(feature = "async")]
+pub mod async_host;
+pub mod compat;
 pub mod config;
 pub mod r#extern;
+pub mod gas_host;
+pub mod host_imports;
 pub mod host_module;
 pub mod instance;
+pub mod instance_pool;
 pub mod isolation;
+pub mod metrics;
 pub mod runtime;
+#[cfg(feature = "experimental")]
+pub mod shared_buffer;
+#[cfg(feature = "otel")]
+pub mod telemetry;
 pub mod types;
 pub mod utils;
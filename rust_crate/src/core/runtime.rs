@@ -1,25 +1,65 @@
 // Copyright (C) 2021-2023 the DTVM authors. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 use crate::core::r#extern::*;
-use std::cell::RefCell;
+use std::any::{Any, TypeId};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::ffi::CStr;
+use std::ops::RangeInclusive;
 use std::rc::Rc;
 
 use super::{
+    abi_version::{check_host_abi_version, CURRENT_HOST_ABI_VERSION},
+    compat::{analyze_contract_compat, CompatReport},
     config::{ZenRuntimeConfig, ZenRuntimeMode},
-    host_module::{ZenHostFuncDesc, ZenHostModule, ZenHostModuleDesc},
+    host_imports::{host_import_usage, HostImportUsage},
+    host_module::{
+        IntoHostFunc, ZenHostFuncDesc, ZenHostFuncSignature, ZenHostModule, ZenHostModuleDesc,
+        HOST_CLOSURE_SLOTS,
+    },
     instance::ZenInstance,
     isolation::ZenIsolation,
     utils::{self, at_least, rust_str_to_c_str, ScopedMalloc},
 };
 
+/// A closure registered with [`ZenRuntime::register_host_closure`].
+#[derive(Clone)]
+pub(crate) struct HostClosure {
+    pub name: Rc<str>,
+    // only read by the telemetry spans
+    #[cfg_attr(not(feature = "otel"), allow(dead_code))]
+    pub category: Rc<str>,
+    pub func: Rc<dyn Any>,
+}
+
+/// Engine objects of a runtime that are still alive, see [`ZenRuntime::live_resources`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ZenRuntimeResources {
+    pub modules: usize,
+    pub isolations: usize,
+    pub instances: usize,
+}
+
 pub struct ZenRuntime {
     pub ptr: *mut ZenRuntimeExtern,
+    mode: ZenRuntimeMode,
 
     // managed resources used in zen runtime.
     // eg ZenHostModuleDesc and ZenHostModule must live until runtime freed
     host_module_descs: RefCell<Vec<Rc<ZenHostModuleDesc>>>,
     host_modules: RefCell<Vec<Rc<ZenHostModule>>>,
+    // signatures of all registered host functions, in registration order
+    host_func_signatures: RefCell<Vec<ZenHostFuncSignature>>,
+    // closures registered as host functions, keyed by host module and function name
+    host_closures: RefCell<HashMap<(String, String), HostClosure>>,
+    // keys of the closures of each closure type, indexed by trampoline slot
+    host_closure_slots: RefCell<HashMap<TypeId, Vec<(String, String)>>>,
+
+    // host abi versions accepted when loading modules, None means no check
+    supported_host_abi_versions: RefCell<Option<RangeInclusive<u32>>>,
+
+    live_resources: Cell<ZenRuntimeResources>,
+    shut_down: Cell<bool>,
 }
 
 impl Drop for ZenRuntime {
@@ -34,21 +74,162 @@ impl Drop for ZenRuntime {
 
 pub const ERROR_BUF_SIZE: usize = 256;
 
+/// Category of host closures registered without one.
+pub const DEFAULT_HOST_CATEGORY: &str = "host";
+
 impl ZenRuntime {
     pub fn new(mode: Option<ZenRuntimeMode>) -> Rc<ZenRuntime> {
-        let config = if let Some(mode) = mode {
-            ZenRuntimeConfig::new(mode)
-        } else {
-            ZenRuntimeConfig::new(ZenRuntimeMode::Singlepass)
-        };
+        let mode = mode.unwrap_or(ZenRuntimeMode::Singlepass);
+        let config = ZenRuntimeConfig::new(mode);
         let ptr = unsafe { ZenCreateRuntime(config.ptr) };
         Rc::new(ZenRuntime {
             ptr,
+            mode,
             host_module_descs: RefCell::new(vec![]),
             host_modules: RefCell::new(vec![]),
+            host_func_signatures: RefCell::new(vec![]),
+            host_closures: RefCell::new(HashMap::new()),
+            host_closure_slots: RefCell::new(HashMap::new()),
+            supported_host_abi_versions: RefCell::new(Some(
+                CURRENT_HOST_ABI_VERSION..=CURRENT_HOST_ABI_VERSION,
+            )),
+            live_resources: Cell::new(ZenRuntimeResources::default()),
+            shut_down: Cell::new(false),
+        })
+    }
+
+    /// The execution mode the runtime was created with.
+    pub fn mode(&self) -> ZenRuntimeMode {
+        self.mode
+    }
+
+    /// Counts of the modules, isolations and instances of this runtime that are still alive.
+    ///
+    /// Every one of them keeps the runtime alive, so a long-lived service can check these are
+    /// back to zero to verify it does not leak engine resources.
+    pub fn live_resources(&self) -> ZenRuntimeResources {
+        self.live_resources.get()
+    }
+
+    pub(crate) fn update_live_resources(&self, update: impl FnOnce(&mut ZenRuntimeResources)) {
+        let mut live = self.live_resources.get();
+        update(&mut live);
+        self.live_resources.set(live);
+    }
+
+    /// Stop the runtime: loading modules, creating isolations or instances and calling into
+    /// existing instances fail with an error from now on.
+    ///
+    /// Engine objects are still released in drop order (instances before their isolation,
+    /// everything before the runtime) once the last reference is gone. Returns an error listing
+    /// the objects still alive, which can then only be dropped.
+    pub fn shutdown(&self) -> Result<(), String> {
+        self.shut_down.set(true);
+        let live = self.live_resources();
+        if live == ZenRuntimeResources::default() {
+            return Ok(());
+        }
+        Err(format!(
+            "runtime shut down with {} modules, {} isolations and {} instances still alive",
+            live.modules, live.isolations, live.instances
+        ))
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.shut_down.get()
+    }
+
+    pub(crate) fn check_running(&self) -> Result<(), String> {
+        if self.is_shut_down() {
+            return Err("runtime is shut down".to_string());
+        }
+        Ok(())
+    }
+
+    /// Refuse to load modules whose `host_abi_version` custom section is outside `versions`,
+    /// by default only [`CURRENT_HOST_ABI_VERSION`]. Modules without the section are still
+    /// accepted.
+    pub fn set_supported_host_abi_versions(&self, versions: RangeInclusive<u32>) {
+        *self.supported_host_abi_versions.borrow_mut() = Some(versions);
+    }
+
+    /// Load modules whatever host ABI version they declare.
+    pub fn disable_host_abi_version_check(&self) {
+        *self.supported_host_abi_versions.borrow_mut() = None;
+    }
+
+    fn check_host_abi_version(&self, code: &[u8]) -> Result<(), String> {
+        match self.supported_host_abi_versions.borrow().as_ref() {
+            Some(versions) => check_host_abi_version(code, versions),
+            None => Ok(()),
+        }
+    }
+
+    /// Register a Rust closure as host function `name` of host module `module` and return its
+    /// descriptor, to be passed to [`ZenRuntime::create_host_module`] for `module` like the
+    /// descriptor of an `extern "C"` function.
+    ///
+    /// The closure receives the calling instance and the wasm arguments; an `Err` is raised in
+    /// the instance, and so is an abort when the instance was not created with context type `C`.
+    /// Each `module` and `name` can only be registered once per runtime. The runtime generates
+    /// the C trampolines per closure type, so at most [`HOST_CLOSURE_SLOTS`] closures of the same
+    /// type (e.g. built by the same helper function) can be registered.
+    pub fn register_host_closure<C, Args, F: IntoHostFunc<C, Args>>(
+        &self,
+        module: &str,
+        name: &str,
+        f: F,
+    ) -> Result<ZenHostFuncDesc, String> {
+        self.register_host_closure_in_category(module, DEFAULT_HOST_CATEGORY, name, f)
+    }
+
+    /// Like [`ZenRuntime::register_host_closure`], filing the host function under `category`,
+    /// e.g. `storage` or `crypto`, which telemetry groups host calls by.
+    pub fn register_host_closure_in_category<C, Args, F: IntoHostFunc<C, Args>>(
+        &self,
+        module: &str,
+        category: &str,
+        name: &str,
+        f: F,
+    ) -> Result<ZenHostFuncDesc, String> {
+        let key = (module.to_string(), name.to_string());
+        let mut host_closures = self.host_closures.borrow_mut();
+        if host_closures.contains_key(&key) {
+            return Err(format!(
+                "host function {module}.{name}: already registered in this runtime"
+            ));
+        }
+        let mut host_closure_slots = self.host_closure_slots.borrow_mut();
+        let slots = host_closure_slots.entry(TypeId::of::<F>()).or_default();
+        let Some(trampoline) = F::trampoline(slots.len()) else {
+            return Err(format!(
+                "host function {module}.{name}: more than {HOST_CLOSURE_SLOTS} closures of its type"
+            ));
+        };
+        slots.push(key.clone());
+        host_closures.insert(
+            key,
+            HostClosure {
+                name: name.into(),
+                category: category.into(),
+                func: Rc::new(f),
+            },
+        );
+        Ok(ZenHostFuncDesc {
+            name: name.to_string(),
+            arg_types: F::arg_types(),
+            ret_types: F::ret_types(),
+            ptr: trampoline,
         })
     }
 
+    /// The closure registered in `slot` of `closure_type`.
+    pub(crate) fn host_closure(&self, closure_type: TypeId, slot: usize) -> Option<HostClosure> {
+        let host_closure_slots = self.host_closure_slots.borrow();
+        let key = host_closure_slots.get(&closure_type)?.get(slot)?;
+        self.host_closures.borrow().get(key).cloned()
+    }
+
     /// <not thread-safe>
     pub fn create_host_module<'a, T: Iterator<Item = &'a ZenHostFuncDesc>>(
         self: &Rc<ZenRuntime>,
@@ -61,9 +242,10 @@ impl ZenRuntime {
         let host_module_desc =
             self.create_host_module_desc(host_module_name, &host_func_descs_refs);
         let host_module_desc = host_module_desc?;
-        if self.host_modules.borrow().len() > 0 {
-            let main_host_module = &self.host_modules.borrow()[0];
-            self.merge_host_module(main_host_module, &host_module_desc);
+        // release the borrow before calling into the engine
+        let main_host_module = self.host_modules.borrow().first().cloned();
+        if let Some(main_host_module) = main_host_module {
+            self.merge_host_module(&main_host_module, &host_module_desc);
         }
         let host_module = self.load_host_module(&host_module_desc);
         let host_module = host_module?;
@@ -73,6 +255,23 @@ impl ZenRuntime {
         Ok(host_module)
     }
 
+    /// Create a host module holding only the functions of `host_func_descs` that `code` imports
+    /// from `host_module_name`.
+    ///
+    /// The returned usage lists the registered functions left out and the imports no registered
+    /// function provides, for policy review before the module is loaded.
+    /// <not thread-safe>
+    pub fn create_minimal_host_module<'a, T: Iterator<Item = &'a ZenHostFuncDesc>>(
+        self: &Rc<ZenRuntime>,
+        host_module_name: &str,
+        host_func_descs: T,
+        code: &[u8],
+    ) -> Result<(Rc<ZenHostModule>, HostImportUsage), String> {
+        let usage = host_import_usage(code, host_module_name, host_func_descs)?;
+        let host_module = self.create_host_module(host_module_name, usage.used.iter(), true)?;
+        Ok((host_module, usage))
+    }
+
     /// <not thread-safe>
     pub fn create_host_module_desc(
         self: &Rc<ZenRuntime>,
@@ -137,6 +336,15 @@ impl ZenRuntime {
             )
         };
 
+        self.host_func_signatures
+            .borrow_mut()
+            .extend(host_func_descs.iter().map(|desc| ZenHostFuncSignature {
+                module: host_module_name.to_string(),
+                name: desc.name.clone(),
+                params: desc.arg_types.clone(),
+                results: desc.ret_types.clone(),
+            }));
+
         let desc = Rc::new(ZenHostModuleDesc {
             rt: RefCell::new(Some(self.clone())),
             ptr,
@@ -147,6 +355,24 @@ impl ZenRuntime {
         Ok(last_desc)
     }
 
+    /// Signatures of every host function registered in this runtime, in registration order.
+    pub fn host_func_signatures(&self) -> Vec<ZenHostFuncSignature> {
+        self.host_func_signatures.borrow().clone()
+    }
+
+    /// JSON listing of every registered host function, for SDK authors and tooling:
+    /// `[{"module":"env","name":"get_host_number","params":["i32","i32"],"results":["i32"]}]`.
+    pub fn describe_host_functions(&self) -> String {
+        serde_json::to_string(&*self.host_func_signatures.borrow())
+            .expect("host function signatures are always serializable")
+    }
+
+    /// Check `bytes` against the metering layer and the host functions registered so far, see
+    /// [`analyze_contract_compat`].
+    pub fn analyze_contract_compat(&self, bytes: &[u8]) -> CompatReport {
+        analyze_contract_compat(bytes, &self.host_func_signatures.borrow())
+    }
+
     /// <not thread-safe>
     pub fn load_host_module(
         self: &Rc<ZenRuntime>,
@@ -183,6 +409,8 @@ impl ZenRuntime {
         module_name: &str,
         code: &[u8],
     ) -> Result<Rc<ZenModule>, String> {
+        self.check_running()?;
+        self.check_host_abi_version(code)?;
         let module_name_c_bytes = rust_str_to_c_str(module_name);
         let module_name_cstr = CStr::from_bytes_until_nul(&module_name_c_bytes).unwrap();
         let mut error_buf: [cty::c_char; ERROR_BUF_SIZE] = [0; ERROR_BUF_SIZE];
@@ -206,6 +434,7 @@ impl ZenRuntime {
                 .unwrap();
             return Err(load_error_str.to_string());
         }
+        self.update_live_resources(|live| live.modules += 1);
         Ok(Rc::new(ZenModule {
             rt: RefCell::new(Some(self.clone())),
             ptr,
@@ -213,6 +442,11 @@ impl ZenRuntime {
     }
 
     pub fn load_module(self: &Rc<Self>, wasm_path: &str) -> Result<Rc<ZenModule>, String> {
+        self.check_running()?;
+        if self.supported_host_abi_versions.borrow().is_some() {
+            let code = std::fs::read(wasm_path).map_err(|err| format!("{wasm_path}: {err}"))?;
+            self.check_host_abi_version(&code)?;
+        }
         let wasm_path_c_bytes = rust_str_to_c_str(wasm_path);
         let wasm_path_cstr = CStr::from_bytes_until_nul(&wasm_path_c_bytes).unwrap();
         let mut error_buf: [cty::c_char; ERROR_BUF_SIZE] = [0; ERROR_BUF_SIZE];
@@ -231,6 +465,7 @@ impl ZenRuntime {
                 .unwrap();
             return Err(load_error_str.to_string());
         }
+        self.update_live_resources(|live| live.modules += 1);
         Ok(Rc::new(ZenModule {
             rt: RefCell::new(Some(self.clone())),
             ptr,
@@ -238,7 +473,9 @@ impl ZenRuntime {
     }
 
     pub fn new_isolation(self: &Rc<Self>) -> Result<Rc<ZenIsolation>, String> {
+        self.check_running()?;
         let ptr = unsafe { ZenCreateIsolation(self.ptr) };
+        self.update_live_resources(|live| live.isolations += 1);
         Ok(Rc::new(ZenIsolation {
             rt: RefCell::new(Some(self.clone())),
             ptr,
@@ -267,6 +504,9 @@ impl Drop for ZenModule {
                 ZenDeleteModule(self.rt.borrow().as_ref().unwrap().ptr, self.ptr);
             }
         }
+        if let Some(rt) = self.rt.borrow().as_ref() {
+            rt.update_live_resources(|live| live.modules -= 1);
+        }
         // remove ref of ZenRuntime
         *self.rt.borrow_mut() = None;
     }
@@ -281,12 +521,13 @@ impl ZenModule {
         let ctx = 0;
         self.new_instance_with_context::<i64>(isolation, gas_limit, ctx)
     }
-    pub fn new_instance_with_context<T: Clone>(
+    pub fn new_instance_with_context<T: Clone + 'static>(
         self: &Rc<Self>,
         isolation: Rc<ZenIsolation>,
         gas_limit: u64,
         ctx: T,
     ) -> Result<Rc<ZenInstance<T>>, String> {
+        self.rt.borrow().as_ref().unwrap().check_running()?;
         let mut error_buf: [cty::c_char; ERROR_BUF_SIZE] = [0; ERROR_BUF_SIZE];
         let ptr = unsafe {
             ZenCreateInstanceWithGas(
@@ -308,6 +549,7 @@ impl ZenModule {
             isolation,
             self,
             ptr,
+            gas_limit,
             ctx,
         ))
     }
@@ -0,0 +1,131 @@
+// Copyright (C) 2021-2025 the DTVM authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Experimental `dtvm_ext.shared_buffer_*` host extension.
+//!
+//! A parent call exposes a region of its memory with `shared_buffer_expose(offset, len)`. The
+//! embedder's cross-contract call host function hands it to the callee instance with
+//! [`set_shared_buffer`] before calling into it, and the callee reads it with
+//! `shared_buffer_size()` and `shared_buffer_read(src_offset, dst, len)`. This is not zero copy:
+//! the region is copied out of the caller's memory when exposed, and every read copies into the
+//! callee's memory. It saves encoding the region into call data and decoding it again.
+//!
+//! Both buffers are scratch values of their instance, so they only live until the outermost
+//! `call_wasm_func` of that instance returns. The ABI may change without notice.
+
+use std::rc::Rc;
+
+use super::host_module::ZenHostFuncDesc;
+use super::instance::ZenInstance;
+use super::r#extern::ZenInstanceExtern;
+use super::types::ZenValueType;
+
+/// Name of the host module providing the shared buffer functions.
+pub const SHARED_BUFFER_MODULE: &str = "dtvm_ext";
+
+/// Buffer exposed by the running call to its callees.
+#[derive(Default)]
+struct ExposedBuffer(Option<Rc<[u8]>>);
+
+/// Buffer exposed to the running call by its caller.
+#[derive(Default)]
+struct ReceivedBuffer(Option<Rc<[u8]>>);
+
+/// The buffer `inst` exposed during the current call, if any.
+pub fn exposed_shared_buffer<T>(inst: &ZenInstance<T>) -> Option<Rc<[u8]>> {
+    inst.try_scratch::<ExposedBuffer>()
+        .ok()
+        .and_then(|exposed| exposed.0.clone())
+}
+
+/// Make `buffer` readable by the next call into `inst`.
+pub fn set_shared_buffer<T>(inst: &ZenInstance<T>, buffer: Rc<[u8]>) {
+    inst.scratch::<ReceivedBuffer>().0 = Some(buffer);
+}
+
+extern "C" fn shared_buffer_expose<T: 'static>(
+    wasm_inst: *mut ZenInstanceExtern,
+    offset: i32,
+    len: i32,
+) {
+    let inst: &ZenInstance<T> = ZenInstance::from_raw_pointer(wasm_inst);
+    if !inst.validate_wasm_addr(offset as u32, len as u32) {
+        inst.raise_out_of_bounds_memory_error();
+        return;
+    }
+    let src = inst.get_host_memory(offset as u32);
+    let bytes: Rc<[u8]> = unsafe { std::slice::from_raw_parts(src, len as u32 as usize) }.into();
+    match inst.try_scratch::<ExposedBuffer>() {
+        Ok(mut exposed) => exposed.0 = Some(bytes),
+        Err(_) => inst.raise_abort_error(),
+    }
+}
+
+/// Returns the size of the received buffer, `-1` when there is none.
+extern "C" fn shared_buffer_size<T: 'static>(wasm_inst: *mut ZenInstanceExtern) -> i32 {
+    let inst: &ZenInstance<T> = ZenInstance::from_raw_pointer(wasm_inst);
+    match inst.try_scratch::<ReceivedBuffer>() {
+        Ok(received) => received.0.as_ref().map_or(-1, |buffer| buffer.len() as i32),
+        Err(_) => {
+            inst.raise_abort_error();
+            -1
+        }
+    }
+}
+
+extern "C" fn shared_buffer_read<T: 'static>(
+    wasm_inst: *mut ZenInstanceExtern,
+    src_offset: i32,
+    dst: i32,
+    len: i32,
+) {
+    let inst: &ZenInstance<T> = ZenInstance::from_raw_pointer(wasm_inst);
+    let buffer = match inst.try_scratch::<ReceivedBuffer>() {
+        Ok(received) => received.0.clone(),
+        Err(_) => {
+            inst.raise_abort_error();
+            return;
+        }
+    };
+    let (src_offset, len) = (src_offset as u32 as usize, len as u32 as usize);
+    let src = buffer.as_ref().and_then(|buffer| {
+        src_offset
+            .checked_add(len)
+            .and_then(|end| buffer.get(src_offset..end))
+    });
+    let Some(src) = src else {
+        inst.raise_out_of_bounds_memory_error();
+        return;
+    };
+    if !inst.validate_wasm_addr(dst as u32, len as u32) {
+        inst.raise_out_of_bounds_memory_error();
+        return;
+    }
+    unsafe {
+        std::ptr::copy_nonoverlapping(src.as_ptr(), inst.get_host_memory(dst as u32), len);
+    }
+}
+
+/// Host functions of the [`SHARED_BUFFER_MODULE`] module, for instances with context type `T`.
+pub fn shared_buffer_host_funcs<T: 'static>() -> Vec<ZenHostFuncDesc> {
+    vec![
+        ZenHostFuncDesc {
+            name: "shared_buffer_expose".to_string(),
+            arg_types: vec![ZenValueType::I32, ZenValueType::I32],
+            ret_types: vec![],
+            ptr: shared_buffer_expose::<T> as *const cty::c_void,
+        },
+        ZenHostFuncDesc {
+            name: "shared_buffer_size".to_string(),
+            arg_types: vec![],
+            ret_types: vec![ZenValueType::I32],
+            ptr: shared_buffer_size::<T> as *const cty::c_void,
+        },
+        ZenHostFuncDesc {
+            name: "shared_buffer_read".to_string(),
+            arg_types: vec![ZenValueType::I32, ZenValueType::I32, ZenValueType::I32],
+            ret_types: vec![],
+            ptr: shared_buffer_read::<T> as *const cty::c_void,
+        },
+    ]
+}
@@ -0,0 +1,147 @@
+// Copyright (C) 2021-2025 the DTVM authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! OpenTelemetry spans around contract execution, enabled by the `otel` feature.
+//!
+//! Every `call_wasm_func` runs in a `dtvm.call` span, or a `dtvm.sub_call` span when it starts
+//! inside a host function, whichever instance that host function belongs to. Call spans record
+//! the function, the argument and return sizes in bytes, the linear memory size after the call,
+//! the gas limit of the instance and the gas used.
+//!
+//! Every closure host function (see
+//! [`ZenRuntime::register_host_closure_in_category`](super::runtime::ZenRuntime::register_host_closure_in_category))
+//! runs in a `dtvm.host_call` span carrying its name and category, so host time can be grouped
+//! by category. `extern "C"` host functions are called by the engine directly and have no span
+//! unless they wrap their body in [`trace_host_call`].
+//!
+//! Spans are created with the global tracer provider and become children of the span current in
+//! the embedding service, so install the service's provider with
+//! `opentelemetry::global::set_tracer_provider`.
+
+use super::instance::ZenInstance;
+use super::types::ZenValue;
+use opentelemetry::trace::{Status, TraceContextExt, Tracer};
+use opentelemetry::{global, Context, KeyValue};
+use std::cell::Cell;
+use std::fmt::Display;
+
+/// Name of the tracer the spans are created with.
+pub const TRACER_NAME: &str = "dtvm";
+
+thread_local! {
+    // host functions running on this thread, across all instances
+    static HOST_CALL_DEPTH: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Integer attribute value of `value`, saturating at `i64::MAX`.
+fn int_value(value: u64) -> i64 {
+    i64::try_from(value).unwrap_or(i64::MAX)
+}
+
+fn values_size(values: &[ZenValue]) -> i64 {
+    values
+        .iter()
+        .map(|value| match value {
+            ZenValue::ZenI32Value(_) | ZenValue::ZenF32Value(_) => 4,
+            ZenValue::ZenI64Value(_) | ZenValue::ZenF64Value(_) => 8,
+        })
+        .sum()
+}
+
+/// Run `call` in a new span named `span_name`, recording the gas it consumed and its error.
+/// `finish` adds attributes known once the call returned.
+fn in_span<T, R, E: Display>(
+    inst: &ZenInstance<T>,
+    span_name: &'static str,
+    attributes: Vec<KeyValue>,
+    call: impl FnOnce() -> Result<R, E>,
+    finish: impl FnOnce(&Result<R, E>) -> Vec<KeyValue>,
+) -> Result<R, E> {
+    let gas_before = inst.get_gas_left();
+    let tracer = global::tracer(TRACER_NAME);
+    let span = tracer
+        .span_builder(span_name)
+        .with_attributes(attributes)
+        .start(&tracer);
+    let cx = Context::current_with_span(span);
+    let result = {
+        // nested calls and host functions become children of this span
+        let _guard = cx.clone().attach();
+        call()
+    };
+    let span = cx.span();
+    let gas_left = inst.get_gas_left();
+    span.set_attribute(KeyValue::new("dtvm.gas_left", int_value(gas_left)));
+    span.set_attribute(KeyValue::new(
+        "dtvm.gas_used",
+        int_value(gas_before.saturating_sub(gas_left)),
+    ));
+    for attribute in finish(&result) {
+        span.set_attribute(attribute);
+    }
+    if let Err(err) = &result {
+        span.set_status(Status::error(err.to_string()));
+    }
+    span.end();
+    result
+}
+
+/// Run a `call_wasm_func` of `func_name` in a call span.
+pub(crate) fn in_call_span<T>(
+    inst: &ZenInstance<T>,
+    func_name: &str,
+    args: &[ZenValue],
+    call: impl FnOnce() -> Result<Vec<ZenValue>, String>,
+) -> Result<Vec<ZenValue>, String> {
+    let nested = HOST_CALL_DEPTH.with(|depth| depth.get() > 0);
+    let span_name = if nested { "dtvm.sub_call" } else { "dtvm.call" };
+    let attributes = vec![
+        KeyValue::new("dtvm.function", func_name.to_string()),
+        KeyValue::new("dtvm.args", int_value(args.len() as u64)),
+        KeyValue::new("dtvm.args_bytes", values_size(args)),
+        KeyValue::new("dtvm.gas_limit", int_value(inst.gas_limit())),
+    ];
+    in_span(inst, span_name, attributes, call, |result| {
+        let mut attributes = vec![KeyValue::new(
+            "dtvm.memory_bytes",
+            int_value(inst.memory_size()),
+        )];
+        if let Ok(results) = result {
+            attributes.push(KeyValue::new("dtvm.results_bytes", values_size(results)));
+        }
+        attributes
+    })
+}
+
+/// Run the host function `name` of `category` in a host call span.
+pub(crate) fn in_host_span<T, R, E: Display>(
+    inst: &ZenInstance<T>,
+    category: &str,
+    name: &str,
+    call: impl FnOnce() -> Result<R, E>,
+) -> Result<R, E> {
+    let attributes = vec![
+        KeyValue::new("dtvm.host_function", name.to_string()),
+        KeyValue::new("dtvm.host_category", category.to_string()),
+    ];
+    HOST_CALL_DEPTH.with(|depth| depth.set(depth.get() + 1));
+    let result = in_span(inst, "dtvm.host_call", attributes, call, |_| vec![]);
+    HOST_CALL_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    result
+}
+
+/// Trace the body of a hand-written `extern "C"` host function like a closure host function:
+/// `call` runs in a `dtvm.host_call` span, and calls into wasm it makes are sub calls.
+pub fn trace_host_call<T, R>(
+    inst: &ZenInstance<T>,
+    category: &str,
+    name: &str,
+    call: impl FnOnce() -> R,
+) -> R {
+    match in_host_span(inst, category, name, || {
+        Ok::<R, std::convert::Infallible>(call())
+    }) {
+        Ok(result) => result,
+        Err(never) => match never {},
+    }
+}
@@ -1,8 +1,11 @@
 // Copyright (C) 2021-2023 the DTVM authors. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 use super::utils::rust_str_to_c_str;
+use serde::Serialize;
+use thiserror::Error;
 
-#[derive(Clone)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ZenValueType {
     I32,
     I64,
@@ -20,6 +23,27 @@ impl ZenValueType {
     }
 }
 
+impl std::fmt::Display for ZenValueType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ZenValueType::I32 => write!(f, "i32"),
+            ZenValueType::I64 => write!(f, "i64"),
+            ZenValueType::F32 => write!(f, "f32"),
+            ZenValueType::F64 => write!(f, "f64"),
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ZenValueError {
+    #[error("expect {expected} value, got {actual:?}")]
+    TypeMismatch {
+        expected: ZenValueType,
+        actual: ZenValue,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq)]
 pub enum ZenValue {
     ZenI32Value(i32),
     ZenI64Value(i64),
@@ -32,8 +56,58 @@ impl ZenValue {
         let value_str = self.to_string();
         rust_str_to_c_str(&value_str)
     }
+
+    pub fn value_type(&self) -> ZenValueType {
+        match self {
+            ZenValue::ZenI32Value(_) => ZenValueType::I32,
+            ZenValue::ZenI64Value(_) => ZenValueType::I64,
+            ZenValue::ZenF32Value(_) => ZenValueType::F32,
+            ZenValue::ZenF64Value(_) => ZenValueType::F64,
+        }
+    }
+
+    fn type_mismatch(&self, expected: ZenValueType) -> ZenValueError {
+        ZenValueError::TypeMismatch {
+            expected,
+            actual: *self,
+        }
+    }
+
+    pub fn as_i32(&self) -> Result<i32, ZenValueError> {
+        match self {
+            ZenValue::ZenI32Value(v) => Ok(*v),
+            _ => Err(self.type_mismatch(ZenValueType::I32)),
+        }
+    }
+
+    pub fn as_i64(&self) -> Result<i64, ZenValueError> {
+        match self {
+            ZenValue::ZenI64Value(v) => Ok(*v),
+            _ => Err(self.type_mismatch(ZenValueType::I64)),
+        }
+    }
+
+    pub fn as_f32(&self) -> Result<f32, ZenValueError> {
+        match self {
+            ZenValue::ZenF32Value(v) => Ok(*v),
+            _ => Err(self.type_mismatch(ZenValueType::F32)),
+        }
+    }
+
+    pub fn as_f64(&self) -> Result<f64, ZenValueError> {
+        match self {
+            ZenValue::ZenF64Value(v) => Ok(*v),
+            _ => Err(self.type_mismatch(ZenValueType::F64)),
+        }
+    }
+
+    /// Interpret an i32 value as a linear memory offset (e.g. a pointer returned by wasm)
+    pub fn as_memory_offset(&self) -> Result<u32, ZenValueError> {
+        self.as_i32().map(|v| v as u32)
+    }
 }
 
+// the plain value is also the argument format expected by the engine, see to_c_str_bytes
 impl std::fmt::Display for ZenValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -44,3 +118,71 @@ impl std::fmt::Display for ZenValue {
         }
     }
 }
+
+/// Typed formatting, e.g. `i32:8 (0x00000008)`, used in assertion failure messages
+impl std::fmt::Debug for ZenValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ZenValue::ZenI32Value(v) => write!(f, "i32:{} ({:#010x})", v, v),
+            ZenValue::ZenI64Value(v) => write!(f, "i64:{} ({:#018x})", v, v),
+            ZenValue::ZenF32Value(v) => write!(f, "f32:{:?} ({:#010x})", v, v.to_bits()),
+            ZenValue::ZenF64Value(v) => write!(f, "f64:{:?} ({:#018x})", v, v.to_bits()),
+        }
+    }
+}
+
+/// Hex of the raw bits, handy for memory offsets and hashes packed in integers
+impl std::fmt::LowerHex for ZenValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ZenValue::ZenI32Value(v) => std::fmt::LowerHex::fmt(v, f),
+            ZenValue::ZenI64Value(v) => std::fmt::LowerHex::fmt(v, f),
+            ZenValue::ZenF32Value(v) => std::fmt::LowerHex::fmt(&v.to_bits(), f),
+            ZenValue::ZenF64Value(v) => std::fmt::LowerHex::fmt(&v.to_bits(), f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zen_value_formatting() {
+        let value = ZenValue::ZenI32Value(255);
+        assert_eq!("255", value.to_string());
+        assert_eq!("i32:255 (0x000000ff)", format!("{:?}", value));
+        assert_eq!("0xff", format!("{:#x}", value));
+        assert_eq!(
+            "i32:-1 (0xffffffff)",
+            format!("{:?}", ZenValue::ZenI32Value(-1))
+        );
+        assert_eq!(
+            "f64:1.5 (0x3ff8000000000000)",
+            format!("{:?}", ZenValue::ZenF64Value(1.5))
+        );
+    }
+
+    #[test]
+    fn test_zen_value_accessors() {
+        assert_eq!(Ok(8), ZenValue::ZenI32Value(8).as_i32());
+        assert_eq!(Ok(1 << 40), ZenValue::ZenI64Value(1 << 40).as_i64());
+        assert_eq!(
+            Ok(0x8000_0000),
+            ZenValue::ZenI32Value(i32::MIN).as_memory_offset()
+        );
+
+        let err = ZenValue::ZenI64Value(8).as_i32().unwrap_err();
+        assert_eq!(
+            ZenValueError::TypeMismatch {
+                expected: ZenValueType::I32,
+                actual: ZenValue::ZenI64Value(8),
+            },
+            err
+        );
+        assert_eq!(
+            "expect i32 value, got i64:8 (0x0000000000000008)",
+            err.to_string()
+        );
+    }
+}
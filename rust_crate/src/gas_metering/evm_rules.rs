@@ -0,0 +1,201 @@
+// Copyright (C) 2021-2025 the DTVM authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! A [`Rules`] implementation approximating the EVM fee schedule.
+//!
+//! Each [`InstructionClass`] is mapped to the EVM opcode tier doing comparable work: constants
+//! and locals cost like `PUSH`/`DUP` (3), arithmetic like `ADD`/`LT` (3), multiplication and
+//! division like `MUL`/`DIV` (5), branches like `JUMP` (8), memory accesses like
+//! `MLOAD`/`MSTORE` (3) and bulk memory operations like the base cost of `MCOPY` (3). Growing
+//! memory costs what the EVM charges for the linear part of expanding memory by one 64 KiB page
+//! (3 gas per 32-byte word). SIMD instructions are priced like floating point ones, override
+//! [`InstructionClass::Simd`] to tune them.
+
+use super::gas_inject::{MemoryGrowCost, Rules};
+use super::instruction_class::{instruction_class, InstructionClass};
+use parity_wasm::elements::Instruction;
+use std::collections::BTreeMap;
+use std::num::NonZeroU32;
+
+/// Default cost of each instruction class.
+fn default_class_cost(class: InstructionClass) -> u32 {
+    match class {
+        InstructionClass::Control => 1,
+        InstructionClass::Branch => 8,
+        InstructionClass::Const => 3,
+        InstructionClass::Local => 3,
+        InstructionClass::Global => 3,
+        InstructionClass::Arithmetic => 3,
+        InstructionClass::Multiplication => 5,
+        InstructionClass::Division => 5,
+        InstructionClass::Conversion => 3,
+        InstructionClass::Float => 10,
+        InstructionClass::MemoryLoad => 3,
+        InstructionClass::MemoryStore => 3,
+        InstructionClass::Memory => 2,
+        InstructionClass::Call => 10,
+        InstructionClass::CallIndirect => 20,
+        InstructionClass::BulkMemory => 3,
+        InstructionClass::Simd => 10,
+    }
+}
+
+/// 2048 words of 32 bytes per 64 KiB page, 3 gas per word.
+const DEFAULT_MEMORY_GROW_COST: u32 = 6144;
+const DEFAULT_CALL_PER_LOCAL_COST: u32 = 1;
+
+/// Gas rules pricing instructions by [`InstructionClass`] with EVM-like costs.
+///
+/// Use [`EvmCostRules::builder`] to override the cost of individual classes.
+#[derive(Debug, Clone)]
+pub struct EvmCostRules {
+    class_costs: BTreeMap<InstructionClass, u32>,
+    memory_grow_cost: u32,
+    call_per_local_cost: u32,
+}
+
+impl Default for EvmCostRules {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl EvmCostRules {
+    pub fn builder() -> EvmCostRulesBuilder {
+        EvmCostRulesBuilder {
+            class_costs: InstructionClass::ALL
+                .iter()
+                .map(|class| (*class, default_class_cost(*class)))
+                .collect(),
+            memory_grow_cost: DEFAULT_MEMORY_GROW_COST,
+            call_per_local_cost: DEFAULT_CALL_PER_LOCAL_COST,
+        }
+    }
+
+    /// Returns the cost of an instruction class.
+    pub fn class_cost(&self, class: InstructionClass) -> u32 {
+        self.class_costs[&class]
+    }
+}
+
+impl Rules for EvmCostRules {
+    fn instruction_cost(&self, instruction: &Instruction) -> Option<u32> {
+        instruction_class(instruction).map(|class| self.class_cost(class))
+    }
+
+    fn memory_grow_cost(&self) -> MemoryGrowCost {
+        NonZeroU32::new(self.memory_grow_cost).map_or(MemoryGrowCost::Free, MemoryGrowCost::Linear)
+    }
+
+    fn call_per_local_cost(&self) -> u32 {
+        self.call_per_local_cost
+    }
+}
+
+/// Builder of [`EvmCostRules`], starting from the default EVM-like costs.
+#[derive(Debug, Clone)]
+pub struct EvmCostRulesBuilder {
+    class_costs: BTreeMap<InstructionClass, u32>,
+    memory_grow_cost: u32,
+    call_per_local_cost: u32,
+}
+
+impl EvmCostRulesBuilder {
+    /// Override the cost of every instruction of `class`.
+    pub fn class_cost(mut self, class: InstructionClass, cost: u32) -> Self {
+        self.class_costs.insert(class, cost);
+        self
+    }
+
+    /// Override the cost per 64 KiB page grown, `0` disables memory growth metering.
+    pub fn memory_grow_cost(mut self, cost: u32) -> Self {
+        self.memory_grow_cost = cost;
+        self
+    }
+
+    /// Override the surcharge per local of a called function.
+    pub fn call_per_local_cost(mut self, cost: u32) -> Self {
+        self.call_per_local_cost = cost;
+        self
+    }
+
+    pub fn build(self) -> EvmCostRules {
+        EvmCostRules {
+            class_costs: self.class_costs,
+            memory_grow_cost: self.memory_grow_cost,
+            call_per_local_cost: self.call_per_local_cost,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gas_metering::GasMeter;
+    use parity_wasm::elements;
+
+    /// The gas charged by the first metered block of the first function
+    fn first_block_charge(wasm_bytes: &[u8]) -> i64 {
+        let module = elements::Module::from_bytes(wasm_bytes).expect("Failed to parse WASM");
+        let body = &module.code_section().unwrap().bodies()[0];
+        match body.code().elements()[0] {
+            Instruction::I64Const(cost) => cost,
+            ref other => panic!("Expected gas charge, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_evm_cost_rules_classes() {
+        let rules = EvmCostRules::default();
+        assert_eq!(Some(3), rules.instruction_cost(&Instruction::I32Add));
+        assert_eq!(Some(5), rules.instruction_cost(&Instruction::I64Mul));
+        assert_eq!(Some(5), rules.instruction_cost(&Instruction::I32DivU));
+        assert_eq!(Some(8), rules.instruction_cost(&Instruction::Br(0)));
+        assert_eq!(Some(3), rules.instruction_cost(&Instruction::I32Load(2, 0)));
+        assert_eq!(
+            Some(3),
+            rules.instruction_cost(&Instruction::SignExt(
+                elements::SignExtInstruction::I32Extend8S
+            ))
+        );
+        assert_eq!(
+            Some(3),
+            rules.instruction_cost(&Instruction::Bulk(elements::BulkInstruction::MemoryFill))
+        );
+        assert_eq!(
+            Some(10),
+            rules.instruction_cost(&Instruction::Simd(elements::SimdInstruction::I32x4Add))
+        );
+        assert_eq!(
+            MemoryGrowCost::Linear(NonZeroU32::new(6144).unwrap()),
+            rules.memory_grow_cost()
+        );
+    }
+
+    #[test]
+    fn test_evm_cost_rules_transform() {
+        let wat = r#"
+            (module
+                (func $test (result i32)
+                    i32.const 6
+                    i32.const 7
+                    i32.mul
+                )
+                (export "test" (func $test))
+            )
+        "#;
+        let wasm_bytes = wat::parse_str(wat).expect("Failed to parse WAT");
+
+        let transformed = GasMeter::transform_with_rules(&wasm_bytes, EvmCostRules::default())
+            .expect("Transform with rules should succeed");
+        assert_eq!(3 + 3 + 5, first_block_charge(&transformed));
+
+        let rules = EvmCostRules::builder()
+            .class_cost(InstructionClass::Multiplication, 50)
+            .build();
+        assert_eq!(50, rules.class_cost(InstructionClass::Multiplication));
+        let transformed = GasMeter::transform_with_rules(&wasm_bytes, rules)
+            .expect("Transform with rules should succeed");
+        assert_eq!(3 + 3 + 50, first_block_charge(&transformed));
+    }
+}
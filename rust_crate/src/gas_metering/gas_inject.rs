@@ -3,18 +3,24 @@
 
 //! This module is used to instrument a Wasm module with the gas metering code.
 //!
-//! The primary public interface is the [`inject`] function which transforms a given
-//! module into one that charges gas for code to be executed. See function documentation for usage
-//! and details.
+//! The primary public interface is the [`inject_with_gas_function`] function which transforms a
+//! given module into one that charges gas for code to be executed. See function documentation for
+//! usage and details.
 
 extern crate alloc;
 
-use alloc::{vec, vec::Vec};
+use alloc::{collections::BTreeMap, string::String, vec, vec::Vec};
 use core::{cmp::min, mem, num::NonZeroU32};
 use parity_wasm::{
     builder,
     elements::{self, Instruction, ValueType},
 };
+use thiserror::Error;
+
+/// Functions whose export or debug name starts with this prefix are metering exempt candidates:
+/// when [`Rules::exempt_function_cost`] returns a cost for them, they are charged that flat cost
+/// on entry instead of per metered block.
+pub const METERING_EXEMPT_PREFIX: &str = "__dtvm_free_";
 
 /// An interface that describes instruction costs.
 pub trait Rules {
@@ -37,6 +43,23 @@ pub trait Rules {
 
     /// A surcharge cost to calling a function that is added per local of that function.
     fn call_per_local_cost(&self) -> u32;
+
+    /// Whether [`Rules::exempt_function_cost`] may return a cost at all. Returning `false`, the
+    /// default, skips looking for exempt functions.
+    fn has_exempt_functions(&self) -> bool {
+        false
+    }
+
+    /// Returns the flat cost of calling function `name`, which starts with
+    /// [`METERING_EXEMPT_PREFIX`] and whose code section entry is `code`, charged on entry
+    /// instead of per metered block. This gives audited library routines a predictable cost.
+    ///
+    /// Names are chosen by the contract author, so an implementation must only exempt code it
+    /// audited, e.g. by comparing `code` with the audited build. Returning `None`, the default,
+    /// meters the function like any other function.
+    fn exempt_function_cost(&self, _name: &str, _code: &[u8]) -> Option<u64> {
+        None
+    }
 }
 
 /// Dynamic costs for memory growth.
@@ -78,6 +101,7 @@ pub struct ConstantCostRules {
     instruction_cost: u32,
     memory_grow_cost: u32,
     call_per_local_cost: u32,
+    exempt_functions: BTreeMap<String, (Vec<u8>, u64)>,
 }
 
 impl ConstantCostRules {
@@ -90,8 +114,17 @@ impl ConstantCostRules {
             instruction_cost,
             memory_grow_cost,
             call_per_local_cost,
+            exempt_functions: BTreeMap::new(),
         }
     }
+
+    /// Charge `cost` once per call of function `name` instead of metering it, as long as its
+    /// code section entry is exactly the audited `code`. Functions with the same name but other
+    /// code are metered as usual, and so are names without the [`METERING_EXEMPT_PREFIX`].
+    pub fn with_exempt_function(mut self, name: &str, code: Vec<u8>, cost: u64) -> Self {
+        self.exempt_functions.insert(name.into(), (code, cost));
+        self
+    }
 }
 
 impl Default for ConstantCostRules {
@@ -101,6 +134,7 @@ impl Default for ConstantCostRules {
             instruction_cost: 1,
             memory_grow_cost: 0,
             call_per_local_cost: 1,
+            exempt_functions: BTreeMap::new(),
         }
     }
 }
@@ -117,6 +151,97 @@ impl Rules for ConstantCostRules {
     fn call_per_local_cost(&self) -> u32 {
         self.call_per_local_cost
     }
+
+    fn has_exempt_functions(&self) -> bool {
+        !self.exempt_functions.is_empty()
+    }
+
+    fn exempt_function_cost(&self, name: &str, code: &[u8]) -> Option<u64> {
+        self.exempt_functions
+            .get(name)
+            .filter(|(audited_code, _)| audited_code.as_slice() == code)
+            .map(|(_, cost)| *cost)
+    }
+}
+
+/// Where the function charging gas comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GasFunction {
+    /// Append an empty local `__instrumented_use_gas` function and export it. The engine
+    /// intercepts its calls, no function index of the module changes.
+    Local,
+    /// Import a `(func (param i64))` host function from `module` under `name`, e.g. `env.gas`.
+    /// Functions defined by the module are shifted by one index.
+    Imported { module: String, name: String },
+}
+
+/// Why [`inject_with_gas_function`] failed.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub(crate) enum InjectError {
+    #[error("module contains an instruction forbidden by the gas rules")]
+    Forbidden,
+    #[error("failed to serialize function {0}")]
+    Serialize(String),
+    #[error("flat cost of metering exempt function {0} exceeds i64::MAX")]
+    ExemptCostOverflow(String),
+}
+
+/// Collect the flat costs of metering exempt functions, keyed by their code section index.
+///
+/// Names come from the export section and, when present, the `name` section. Costs above
+/// `i64::MAX` can't be passed to the gas function and fail the instrumentation.
+fn exempt_function_costs<R: Rules>(
+    module: &elements::Module,
+    rules: &R,
+) -> Result<BTreeMap<usize, i64>, InjectError> {
+    if !rules.has_exempt_functions() {
+        return Ok(BTreeMap::new());
+    }
+    let mut names: Vec<(u32, String)> = Vec::new();
+    if let Some(export_section) = module.export_section() {
+        for export in export_section.entries() {
+            if let elements::Internal::Function(func_idx) = export.internal() {
+                names.push((*func_idx, export.field().to_string()));
+            }
+        }
+    }
+    let parsed_names;
+    let names_section = match module.names_section() {
+        Some(names_section) => Some(names_section),
+        None if module.custom_sections().any(|s| s.name() == "name") => {
+            parsed_names = module.clone().parse_names().ok();
+            parsed_names.as_ref().and_then(|m| m.names_section())
+        }
+        None => None,
+    };
+    if let Some(func_names) = names_section.and_then(|s| s.functions()) {
+        for (func_idx, name) in func_names.names().iter() {
+            names.push((func_idx, name.clone()));
+        }
+    }
+
+    let imported_funcs = module.import_count(elements::ImportCountType::Function) as u32;
+    let bodies = module.code_section().map_or(&[][..], |s| s.bodies());
+    let mut costs = BTreeMap::new();
+    for (func_idx, name) in names {
+        if !name.starts_with(METERING_EXEMPT_PREFIX) {
+            continue;
+        }
+        let Some(body) = func_idx
+            .checked_sub(imported_funcs)
+            .and_then(|body_idx| bodies.get(body_idx as usize))
+        else {
+            continue;
+        };
+        let code =
+            elements::serialize(body.clone()).map_err(|_| InjectError::Serialize(name.clone()))?;
+        if let Some(cost) = rules.exempt_function_cost(&name, &code) {
+            let cost =
+                i64::try_from(cost).map_err(|_| InjectError::ExemptCostOverflow(name.clone()))?;
+            costs.insert((func_idx - imported_funcs) as usize, cost);
+        }
+    }
+    Ok(costs)
 }
 
 /// Transforms a given module into one that tracks the gas charged during its execution.
@@ -145,53 +270,92 @@ impl Rules for ConstantCostRules {
 /// the block level gas charges as the gas cost is not static and depends on the stack argument
 /// to `memory.grow`.
 ///
+/// Functions named with the [`METERING_EXEMPT_PREFIX`] for which [`Rules::exempt_function_cost`]
+/// returns a cost are not divided into metered blocks. They are charged that flat cost on entry
+/// instead, while `memory.grow` inside them is still charged as above.
+///
 /// The above transformations are performed for every function body defined in the module. This
 /// function also rewrites all function indices references by code, table elements, etc., since
 /// the addition of an imported functions changes the indices of module-defined functions. If
 /// the module has a `NameSection`, added by calling `parse_names`, the indices will also be
 /// updated.
 ///
-/// Syncronizing the amount of gas charged with the execution engine can be done in two ways. The
-/// first way is by calling the imported `gas` host function, see [`host_function`] for details. The
-/// second way is by using a local `gas` function together with a mutable global, see
-/// [`mutable_global`] for details.
+/// Syncronizing the amount of gas charged with the execution engine can be done in two ways, see
+/// [`GasFunction`]. With [`GasFunction::Imported`] the gas function is imported after the
+/// existing function imports, like wasm-instrument does. Every reference to a module defined
+/// function is rewritten to account for the shifted indices: `call` instructions, exports,
+/// element segments, the start function and the `name` section.
 ///
 /// This routine runs in time linear in the size of the input module.
 ///
 /// The function fails if the module contains any operation forbidden by gas rule set, returning
-/// the original module as an `Err`.
-pub fn inject<R: Rules>(
+/// the original module and the reason as an `Err`.
+pub(crate) fn inject_with_gas_function<R: Rules>(
     module: elements::Module,
     rules: &R,
-) -> Result<elements::Module, elements::Module> {
+    gas_function: &GasFunction,
+) -> Result<elements::Module, (elements::Module, InjectError)> {
     let functions_space = module.functions_space() as u32;
+    let exempt_costs = match exempt_function_costs(&module, rules) {
+        Ok(exempt_costs) => exempt_costs,
+        Err(err) => return Err((module, err)),
+    };
 
     let mut mbuilder = builder::from_module(module.clone());
 
-    // Inject the export for `__instrumented_use_gas`
     let gas_func_sig = builder::SignatureBuilder::new()
         .with_param(ValueType::I64)
         .build_sig();
 
-    let function = builder::FunctionBuilder::new()
-        .with_signature(gas_func_sig)
-        .body()
-        .with_instructions(elements::Instructions::new(vec![Instruction::End]))
-        .build()
-        .build();
-
-    // Inject local gas function
-    mbuilder.push_function(function);
-
-    // Inject the export entry for the gas counting function
-    let gas_func_idx = functions_space;
-    mbuilder.push_export(
-        builder::export()
-            .field("__instrumented_use_gas")
-            .internal()
-            .func(gas_func_idx)
-            .build(),
-    );
+    let (gas_func_idx, gas_func_name) = match gas_function {
+        GasFunction::Local => {
+            let function = builder::FunctionBuilder::new()
+                .with_signature(gas_func_sig)
+                .body()
+                .with_instructions(elements::Instructions::new(vec![Instruction::End]))
+                .build()
+                .build();
+
+            // Inject local gas function
+            mbuilder.push_function(function);
+
+            // Inject the export entry for the gas counting function
+            let gas_func_idx = functions_space;
+            mbuilder.push_export(
+                builder::export()
+                    .field(GAS_FUNC_NAME)
+                    .internal()
+                    .func(gas_func_idx)
+                    .build(),
+            );
+            (gas_func_idx, String::from(GAS_FUNC_NAME))
+        }
+        GasFunction::Imported {
+            module: import_module,
+            name,
+        } => {
+            let gas_func_type = mbuilder.push_signature(gas_func_sig);
+            mbuilder.push_import(
+                builder::import()
+                    .module(import_module)
+                    .field(name)
+                    .external()
+                    .func(gas_func_type)
+                    .build(),
+            );
+            // The new import goes after the existing function imports
+            let gas_func_idx = module.import_count(elements::ImportCountType::Function) as u32;
+            (gas_func_idx, name.clone())
+        }
+    };
+    let imported = matches!(gas_function, GasFunction::Imported { .. });
+    let remap_func_idx = |func_idx: u32| {
+        if imported && func_idx >= gas_func_idx {
+            func_idx + 1
+        } else {
+            func_idx
+        }
+    };
 
     // Gas function cost is 0 since it's an empty function and its cost is self-accounted.
     let gas_fn_cost = 0;
@@ -206,27 +370,41 @@ pub fn inject<R: Rules>(
     'outer: for section in resulting_module.sections_mut() {
         match section {
             elements::Section::Code(code_section) => {
-                // Don't inject counters to the gas function itself, which is the last one.
+                // Don't inject counters to a local gas function, which is the last one.
                 let len = code_section.bodies().len();
-                let injection_targets = &mut code_section.bodies_mut()[..len - 1];
-
-                for func_body in injection_targets {
-                    result = func_body
-                        .locals()
-                        .iter()
-                        .try_fold(0u32, |count, val_type| count.checked_add(val_type.count()))
-                        .ok_or(())
-                        .and_then(|locals_count| {
-                            inject_counter(
-                                func_body.code_mut(),
-                                gas_fn_cost,
-                                locals_count,
-                                rules,
-                                gas_func_idx,
-                            )
-                        });
-                    if result.is_err() {
-                        break 'outer;
+                let injection_targets = match gas_function {
+                    GasFunction::Local => &mut code_section.bodies_mut()[..len - 1],
+                    GasFunction::Imported { .. } => &mut code_section.bodies_mut()[..],
+                };
+
+                for (body_idx, func_body) in injection_targets.iter_mut().enumerate() {
+                    if imported {
+                        for instruction in func_body.code_mut().elements_mut() {
+                            if let Instruction::Call(call_idx) = instruction {
+                                *call_idx = remap_func_idx(*call_idx);
+                            }
+                        }
+                    }
+                    if let Some(cost) = exempt_costs.get(&body_idx) {
+                        inject_flat_charge(func_body.code_mut(), *cost, gas_func_idx);
+                    } else {
+                        result = func_body
+                            .locals()
+                            .iter()
+                            .try_fold(0u32, |count, val_type| count.checked_add(val_type.count()))
+                            .ok_or(())
+                            .and_then(|locals_count| {
+                                inject_counter(
+                                    func_body.code_mut(),
+                                    gas_fn_cost,
+                                    locals_count,
+                                    rules,
+                                    gas_func_idx,
+                                )
+                            });
+                        if result.is_err() {
+                            break 'outer;
+                        }
                     }
                     if rules.memory_grow_cost().enabled()
                         && inject_grow_counter(func_body.code_mut(), total_func) > 0
@@ -235,19 +413,94 @@ pub fn inject<R: Rules>(
                     }
                 }
             }
-            // Since we are adding a function to the end of the function space,
-            // we don't need to adjust any indices.
+            // A local gas function is added to the end of the function space and needs no
+            // index adjustment, an imported one shifts the module defined functions.
+            elements::Section::Export(export_section) if imported => {
+                for export in export_section.entries_mut() {
+                    if let elements::Internal::Function(func_idx) = export.internal_mut() {
+                        *func_idx = remap_func_idx(*func_idx);
+                    }
+                }
+            }
+            elements::Section::Element(element_section) if imported => {
+                for segment in element_section.entries_mut() {
+                    for func_idx in segment.members_mut() {
+                        *func_idx = remap_func_idx(*func_idx);
+                    }
+                }
+            }
+            elements::Section::Start(start_idx) if imported => {
+                *start_idx = remap_func_idx(*start_idx);
+            }
             _ => {}
         }
     }
 
-    result.map_err(|_| module)?;
+    result.map_err(|_| (module, InjectError::Forbidden))?;
+
+    let mut injected_names = vec![(gas_func_idx, gas_func_name.as_str())];
+    let resulting_module = if need_grow_counter {
+        injected_names.push((total_func, GROW_COUNTER_FUNC_NAME));
+        add_grow_counter(resulting_module, rules, gas_func_idx)
+    } else {
+        resulting_module
+    };
+
+    Ok(update_names_section(
+        resulting_module,
+        remap_func_idx,
+        &injected_names,
+    ))
+}
+
+/// Debug name of the injected gas function, also used as its export name.
+const GAS_FUNC_NAME: &str = "__instrumented_use_gas";
+/// Debug name of the injected function charging for `memory.grow`.
+const GROW_COUNTER_FUNC_NAME: &str = "__instrumented_grow_counter";
 
-    if need_grow_counter {
-        Ok(add_grow_counter(resulting_module, rules, gas_func_idx))
+/// Keep the `name` section in sync with the instrumented module.
+///
+/// Function indices of the original module are moved with `remap_func_idx` (in function and
+/// local name maps) and the `injected` functions are named, so debuggers keep showing correct
+/// function names. Modules without a `name` section, or with one that fails to parse, are
+/// returned unchanged.
+fn update_names_section<F: Fn(u32) -> u32>(
+    module: elements::Module,
+    remap_func_idx: F,
+    injected: &[(u32, &str)],
+) -> elements::Module {
+    let has_name_section = module
+        .custom_sections()
+        .any(|section| section.name() == "name");
+    let mut module = if module.names_section().is_none() && has_name_section {
+        match module.parse_names() {
+            Ok(module) => module,
+            Err((_, module)) => return module,
+        }
     } else {
-        Ok(resulting_module)
+        module
+    };
+
+    if let Some(names_section) = module.names_section_mut() {
+        if let Some(functions) = names_section.functions_mut() {
+            let names = functions.names_mut();
+            let original = mem::replace(names, elements::IndexMap::with_capacity(0));
+            for (func_idx, name) in original.iter() {
+                names.insert(remap_func_idx(func_idx), name.clone());
+            }
+            for (func_idx, name) in injected {
+                names.insert(*func_idx, String::from(*name));
+            }
+        }
+        if let Some(locals) = names_section.locals_mut() {
+            let local_names = locals.local_names_mut();
+            let original = mem::replace(local_names, elements::IndexMap::with_capacity(0));
+            for (func_idx, names) in original.iter() {
+                local_names.insert(remap_func_idx(func_idx), names.clone());
+            }
+        }
     }
+    module
 }
 
 /// A control flow block is opened with the `block`, `loop`, and `if` instructions and is closed
@@ -453,6 +706,13 @@ impl Counter {
     }
 }
 
+/// Charge `cost` once at the function entry, used for metering exempt functions.
+fn inject_flat_charge(instructions: &mut elements::Instructions, cost: i64, gas_func: u32) {
+    let code = instructions.elements_mut();
+    code.insert(0, Instruction::Call(gas_func));
+    code.insert(0, Instruction::I64Const(cost));
+}
+
 fn inject_grow_counter(instructions: &mut elements::Instructions, grow_counter_func: u32) -> usize {
     use parity_wasm::elements::Instruction::*;
     let mut counter = 0;
@@ -0,0 +1,128 @@
+// Copyright (C) 2021-2025 the DTVM authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Grouping of wasm instructions into classes that are priced together.
+
+use parity_wasm::elements::Instruction;
+use serde::{Deserialize, Serialize};
+
+/// A class of instructions with similar execution cost.
+///
+/// Cost tables name classes in snake case, e.g. `call_indirect`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstructionClass {
+    /// Structured control and stack bookkeeping: `nop`, `block`, `loop`, `if`, `else`, `end`,
+    /// `drop`, `select`, `unreachable`.
+    Control,
+    /// `br`, `br_if`, `br_table` and `return`.
+    Branch,
+    /// Integer constants.
+    Const,
+    /// `local.get`, `local.set` and `local.tee`.
+    Local,
+    /// `global.get` and `global.set`.
+    Global,
+    /// Integer add/sub, comparisons, bitwise operations, shifts and bit counting.
+    Arithmetic,
+    /// Integer multiplication.
+    Multiplication,
+    /// Integer division and remainder.
+    Division,
+    /// Integer width conversions: `i32.wrap_i64`, `i64.extend_i32_s/u` and the sign-extension
+    /// operators `i32.extend8_s` etc.
+    Conversion,
+    /// Any instruction producing or consuming floating point values.
+    Float,
+    /// Linear memory loads.
+    MemoryLoad,
+    /// Linear memory stores.
+    MemoryStore,
+    /// `memory.size` and `memory.grow`.
+    Memory,
+    /// Direct calls.
+    Call,
+    /// Indirect calls through a table.
+    CallIndirect,
+    /// Bulk memory operations: `memory.copy`, `memory.fill`, `memory.init`, `data.drop` and
+    /// `table.init`, `table.copy`, `elem.drop`. The cost is per instruction, independent of the
+    /// number of bytes or elements moved.
+    BulkMemory,
+    /// Every `v128` instruction of the fixed-width SIMD proposal. They are priced as a single
+    /// class, so contracts built with `-msimd128` can be metered before per-lane costs exist.
+    Simd,
+}
+
+impl InstructionClass {
+    /// All classes, in declaration order.
+    pub const ALL: [InstructionClass; 17] = [
+        InstructionClass::Control,
+        InstructionClass::Branch,
+        InstructionClass::Const,
+        InstructionClass::Local,
+        InstructionClass::Global,
+        InstructionClass::Arithmetic,
+        InstructionClass::Multiplication,
+        InstructionClass::Division,
+        InstructionClass::Conversion,
+        InstructionClass::Float,
+        InstructionClass::MemoryLoad,
+        InstructionClass::MemoryStore,
+        InstructionClass::Memory,
+        InstructionClass::Call,
+        InstructionClass::CallIndirect,
+        InstructionClass::BulkMemory,
+        InstructionClass::Simd,
+    ];
+}
+
+/// Returns the class of `instruction`, or `None` for instructions of proposals no class
+/// covers yet.
+pub fn instruction_class(instruction: &Instruction) -> Option<InstructionClass> {
+    use parity_wasm::elements::Instruction::*;
+    let class = match instruction {
+        Unreachable | Nop | Block(_) | Loop(_) | If(_) | Else | End | Drop | Select => {
+            InstructionClass::Control
+        }
+        Br(_) | BrIf(_) | BrTable(_) | Return => InstructionClass::Branch,
+        Call(_) => InstructionClass::Call,
+        CallIndirect(..) => InstructionClass::CallIndirect,
+        GetLocal(_) | SetLocal(_) | TeeLocal(_) => InstructionClass::Local,
+        GetGlobal(_) | SetGlobal(_) => InstructionClass::Global,
+        I32Load(..) | I64Load(..) | I32Load8S(..) | I32Load8U(..) | I32Load16S(..)
+        | I32Load16U(..) | I64Load8S(..) | I64Load8U(..) | I64Load16S(..) | I64Load16U(..)
+        | I64Load32S(..) | I64Load32U(..) => InstructionClass::MemoryLoad,
+        I32Store(..) | I64Store(..) | I32Store8(..) | I32Store16(..) | I64Store8(..)
+        | I64Store16(..) | I64Store32(..) => InstructionClass::MemoryStore,
+        CurrentMemory(_) | GrowMemory(_) => InstructionClass::Memory,
+        I32Const(_) | I64Const(_) => InstructionClass::Const,
+        I32Eqz | I32Eq | I32Ne | I32LtS | I32LtU | I32GtS | I32GtU | I32LeS | I32LeU | I32GeS
+        | I32GeU | I64Eqz | I64Eq | I64Ne | I64LtS | I64LtU | I64GtS | I64GtU | I64LeS | I64LeU
+        | I64GeS | I64GeU | I32Clz | I32Ctz | I32Popcnt | I32Add | I32Sub | I32And | I32Or
+        | I32Xor | I32Shl | I32ShrS | I32ShrU | I32Rotl | I32Rotr | I64Clz | I64Ctz | I64Popcnt
+        | I64Add | I64Sub | I64And | I64Or | I64Xor | I64Shl | I64ShrS | I64ShrU | I64Rotl
+        | I64Rotr => InstructionClass::Arithmetic,
+        I32Mul | I64Mul => InstructionClass::Multiplication,
+        I32DivS | I32DivU | I32RemS | I32RemU | I64DivS | I64DivU | I64RemS | I64RemU => {
+            InstructionClass::Division
+        }
+        I32WrapI64 | I64ExtendSI32 | I64ExtendUI32 | SignExt(_) => InstructionClass::Conversion,
+        F32Load(..) | F64Load(..) | F32Store(..) | F64Store(..) | F32Const(_) | F64Const(_)
+        | F32Eq | F32Ne | F32Lt | F32Gt | F32Le | F32Ge | F64Eq | F64Ne | F64Lt | F64Gt | F64Le
+        | F64Ge | F32Abs | F32Neg | F32Ceil | F32Floor | F32Trunc | F32Nearest | F32Sqrt
+        | F32Add | F32Sub | F32Mul | F32Div | F32Min | F32Max | F32Copysign | F64Abs | F64Neg
+        | F64Ceil | F64Floor | F64Trunc | F64Nearest | F64Sqrt | F64Add | F64Sub | F64Mul
+        | F64Div | F64Min | F64Max | F64Copysign | I32TruncSF32 | I32TruncUF32 | I32TruncSF64
+        | I32TruncUF64 | I64TruncSF32 | I64TruncUF32 | I64TruncSF64 | I64TruncUF64
+        | F32ConvertSI32 | F32ConvertUI32 | F32ConvertSI64 | F32ConvertUI64 | F32DemoteF64
+        | F64ConvertSI32 | F64ConvertUI32 | F64ConvertSI64 | F64ConvertUI64 | F64PromoteF32
+        | I32ReinterpretF32 | I64ReinterpretF64 | F32ReinterpretI32 | F64ReinterpretI64 => {
+            InstructionClass::Float
+        }
+        Bulk(_) => InstructionClass::BulkMemory,
+        Simd(_) => InstructionClass::Simd,
+        #[allow(unreachable_patterns)]
+        _ => return None,
+    };
+    Some(class)
+}
@@ -1,9 +1,22 @@
 // Copyright (C) 2021-2025 the DTVM authors. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod evm_rules;
+pub use evm_rules::{EvmCostRules, EvmCostRulesBuilder};
 mod gas_inject;
-pub use gas_inject::{ConstantCostRules, Rules};
+pub use gas_inject::{
+    ConstantCostRules, GasFunction, MemoryGrowCost, Rules, METERING_EXEMPT_PREFIX,
+};
+pub mod instruction_class;
+pub use instruction_class::{instruction_class, InstructionClass};
+pub mod pass;
+pub use pass::{
+    CoveragePass, FloatPolicyPass, GasMeteringPass, MemoryLimitPass, ModulePass, PassPipeline,
+    StackLimitPass, COVERAGE_EXPORT,
+};
+pub mod table_rules;
+pub use table_rules::{CostTableError, TableCostRules};
 pub mod transform;
-pub use transform::GasMeter;
+pub use transform::{check_size_budget, GasMeter, SizeReport, TransformError};
 #[cfg(test)]
 mod validation;
@@ -0,0 +1,648 @@
+// Copyright (C) 2021-2025 the DTVM authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Instrumentation passes.
+//!
+//! A [`PassPipeline`] parses the input module once, runs every [`ModulePass`] over the shared
+//! `parity_wasm` module in the order they were added and serializes the result once. Gas
+//! metering is one pass among others, so embedders can add their own passes (before or after
+//! metering) without patching this crate.
+//!
+//! Besides [`GasMeteringPass`], this module provides [`FloatPolicyPass`], [`MemoryLimitPass`],
+//! [`StackLimitPass`] and [`CoveragePass`]. [`GasMeter`](super::GasMeter) runs a pipeline with
+//! metering only.
+
+use super::gas_inject::{inject_with_gas_function, GasFunction, Rules};
+use super::instruction_class::{instruction_class, InstructionClass};
+use super::transform::{check_size_budget, TransformError};
+use parity_wasm::{
+    builder,
+    elements::{self, BlockType, Instruction, ValueType},
+    serialize,
+};
+
+/// A transformation of a parsed module.
+pub trait ModulePass {
+    /// Name used in error messages.
+    fn name(&self) -> &str;
+
+    /// Transform `module`, returning a reason on failure.
+    fn run(&self, module: elements::Module) -> Result<elements::Module, String>;
+}
+
+/// Ordered list of passes applied to a module.
+#[derive(Default)]
+pub struct PassPipeline {
+    passes: Vec<Box<dyn ModulePass>>,
+    size_budget: Option<usize>,
+}
+
+impl PassPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append `pass` to the end of the pipeline.
+    pub fn with_pass<P: ModulePass + 'static>(mut self, pass: P) -> Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    /// Fail [`PassPipeline::transform`] when the output exceeds `budget` bytes, e.g. the
+    /// on-chain code size limit, instead of letting the deployment fail later.
+    pub fn with_size_budget(mut self, budget: usize) -> Self {
+        self.size_budget = Some(budget);
+        self
+    }
+
+    /// Names of the passes in the order they run.
+    pub fn pass_names(&self) -> Vec<&str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    /// Run all passes over an already parsed module.
+    pub fn run_module(
+        &self,
+        mut module: elements::Module,
+    ) -> Result<elements::Module, TransformError> {
+        for pass in &self.passes {
+            module = pass
+                .run(module)
+                .map_err(|reason| TransformError::Pass(pass.name().to_string(), reason))?;
+        }
+        Ok(module)
+    }
+
+    /// Parse `input_wasm`, run all passes and serialize the result.
+    pub fn transform(&self, input_wasm: &[u8]) -> Result<Vec<u8>, TransformError> {
+        let module = elements::Module::from_bytes(input_wasm).map_err(TransformError::Parse)?;
+        let module = self.run_module(module)?;
+        let output = serialize(module).map_err(TransformError::Serialize)?;
+        if let Some(budget) = self.size_budget {
+            check_size_budget(input_wasm, &output, budget)?;
+        }
+        Ok(output)
+    }
+}
+
+/// Gas metering as a pass, see [`inject_with_gas_function`].
+pub struct GasMeteringPass<R: Rules> {
+    rules: R,
+    gas_function: GasFunction,
+}
+
+impl<R: Rules> GasMeteringPass<R> {
+    pub fn new(rules: R) -> Self {
+        Self {
+            rules,
+            gas_function: GasFunction::Local,
+        }
+    }
+
+    /// Charge gas through `gas_function` instead of a local gas function.
+    pub fn with_gas_function(mut self, gas_function: GasFunction) -> Self {
+        self.gas_function = gas_function;
+        self
+    }
+}
+
+impl<R: Rules> ModulePass for GasMeteringPass<R> {
+    fn name(&self) -> &str {
+        "gas_metering"
+    }
+
+    fn run(&self, module: elements::Module) -> Result<elements::Module, String> {
+        inject_with_gas_function(module, &self.rules, &self.gas_function)
+            .map_err(|(_, err)| err.to_string())
+    }
+}
+
+/// Rejects modules using floating point types or instructions, whose results may differ
+/// between platforms.
+pub struct FloatPolicyPass;
+
+fn is_float_type(value_type: &ValueType) -> bool {
+    matches!(value_type, ValueType::F32 | ValueType::F64)
+}
+
+fn is_float_instruction(instruction: &Instruction) -> bool {
+    instruction_class(instruction) == Some(InstructionClass::Float)
+}
+
+impl ModulePass for FloatPolicyPass {
+    fn name(&self) -> &str {
+        "float_policy"
+    }
+
+    fn run(&self, module: elements::Module) -> Result<elements::Module, String> {
+        if let Some(type_section) = module.type_section() {
+            for elements::Type::Function(func_type) in type_section.types() {
+                if func_type
+                    .params()
+                    .iter()
+                    .chain(func_type.results())
+                    .any(is_float_type)
+                {
+                    return Err("function signature uses a floating point type".to_string());
+                }
+            }
+        }
+        if let Some(global_section) = module.global_section() {
+            if global_section
+                .entries()
+                .iter()
+                .any(|global| is_float_type(&global.global_type().content_type()))
+            {
+                return Err("global uses a floating point type".to_string());
+            }
+        }
+        if let Some(code_section) = module.code_section() {
+            for (func_index, body) in code_section.bodies().iter().enumerate() {
+                if body
+                    .locals()
+                    .iter()
+                    .any(|local| is_float_type(&local.value_type()))
+                {
+                    return Err(format!(
+                        "function #{func_index} declares a floating point local"
+                    ));
+                }
+                if body.code().elements().iter().any(is_float_instruction) {
+                    return Err(format!(
+                        "function #{func_index} uses a floating point instruction"
+                    ));
+                }
+            }
+        }
+        Ok(module)
+    }
+}
+
+/// Caps the linear memory at `max_pages` 64 KiB pages, below the maximum the module
+/// declares, so `memory.grow` past the cap returns `-1` instead of allocating.
+///
+/// The engine has no per-instance memory limit, so the cap is part of the instrumented module:
+/// instrument the module once per cap, e.g. a tighter one for fuzzing.
+pub struct MemoryLimitPass {
+    max_pages: u32,
+}
+
+impl MemoryLimitPass {
+    pub fn new(max_pages: u32) -> Self {
+        Self { max_pages }
+    }
+
+    fn limit(&self, memory_type: &elements::MemoryType) -> Result<elements::MemoryType, String> {
+        let limits = memory_type.limits();
+        if limits.initial() > self.max_pages {
+            return Err(format!(
+                "memory starts with {} pages, more than the limit of {} pages",
+                limits.initial(),
+                self.max_pages
+            ));
+        }
+        let maximum = limits
+            .maximum()
+            .map_or(self.max_pages, |maximum| maximum.min(self.max_pages));
+        Ok(elements::MemoryType::new(limits.initial(), Some(maximum)))
+    }
+}
+
+impl ModulePass for MemoryLimitPass {
+    fn name(&self) -> &str {
+        "memory_limit"
+    }
+
+    fn run(&self, mut module: elements::Module) -> Result<elements::Module, String> {
+        if let Some(import_section) = module.import_section_mut() {
+            for import in import_section.entries_mut() {
+                if let elements::External::Memory(memory_type) = import.external_mut() {
+                    *memory_type = self.limit(memory_type)?;
+                }
+            }
+        }
+        if let Some(memory_section) = module.memory_section_mut() {
+            for memory_type in memory_section.entries_mut() {
+                *memory_type = self.limit(memory_type)?;
+            }
+        }
+        Ok(module)
+    }
+}
+
+fn mutable_global(value_type: ValueType, init: Instruction) -> elements::GlobalEntry {
+    elements::GlobalEntry::new(
+        elements::GlobalType::new(value_type, true),
+        elements::InitExpr::new(vec![init, Instruction::End]),
+    )
+}
+
+/// Traps with `unreachable` once calls nest deeper than `max_depth`, instead of relying on the
+/// native stack of the engine.
+///
+/// Every `call` and `call_indirect` increments a mutable global holding the call depth before
+/// the call and decrements it after, so host functions calling back into the module count as
+/// one level. A trap leaves the depth where it was: don't reuse an instance after a trapped
+/// call. Run the pass before [`GasMeteringPass`] so the added code is charged.
+pub struct StackLimitPass {
+    max_depth: u32,
+}
+
+impl StackLimitPass {
+    pub fn new(max_depth: u32) -> Self {
+        Self { max_depth }
+    }
+}
+
+impl ModulePass for StackLimitPass {
+    fn name(&self) -> &str {
+        "stack_limit"
+    }
+
+    fn run(&self, module: elements::Module) -> Result<elements::Module, String> {
+        let depth = module.globals_space() as u32;
+        let mut module = builder::from_module(module)
+            .with_global(mutable_global(ValueType::I32, Instruction::I32Const(0)))
+            .build();
+        // compared unsigned, only the bit pattern matters
+        let max_depth = self.max_depth as i32;
+        if let Some(code_section) = module.code_section_mut() {
+            for body in code_section.bodies_mut() {
+                let code = body.code_mut().elements_mut();
+                let mut instrumented = Vec::with_capacity(code.len());
+                for instruction in code.drain(..) {
+                    if !matches!(
+                        instruction,
+                        Instruction::Call(_) | Instruction::CallIndirect(..)
+                    ) {
+                        instrumented.push(instruction);
+                        continue;
+                    }
+                    instrumented.extend([
+                        Instruction::GetGlobal(depth),
+                        Instruction::I32Const(1),
+                        Instruction::I32Add,
+                        Instruction::SetGlobal(depth),
+                        Instruction::GetGlobal(depth),
+                        Instruction::I32Const(max_depth),
+                        Instruction::I32GtU,
+                        Instruction::If(BlockType::NoResult),
+                        Instruction::Unreachable,
+                        Instruction::End,
+                        instruction,
+                        Instruction::GetGlobal(depth),
+                        Instruction::I32Const(1),
+                        Instruction::I32Sub,
+                        Instruction::SetGlobal(depth),
+                    ]);
+                }
+                *code = instrumented;
+            }
+        }
+        Ok(module)
+    }
+}
+
+/// Name of the function [`CoveragePass`] exports.
+pub const COVERAGE_EXPORT: &str = "__dtvm_coverage_count";
+
+/// Counts how many times every function defined by the module is entered, for coverage
+/// reports of contract test suites.
+///
+/// Every function gets a mutable `i64` global incremented on entry, and the module exports
+/// [`COVERAGE_EXPORT`]`(func_index: i32) -> i64` returning the count of function `func_index`
+/// in the function index space, imports included. Unknown indices count `0`.
+pub struct CoveragePass;
+
+impl ModulePass for CoveragePass {
+    fn name(&self) -> &str {
+        "coverage"
+    }
+
+    fn run(&self, module: elements::Module) -> Result<elements::Module, String> {
+        let imported_funcs = module.import_count(elements::ImportCountType::Function) as u32;
+        let defined_funcs = module
+            .function_section()
+            .map_or(0, |section| section.entries().len()) as u32;
+        let first_counter = module.globals_space() as u32;
+        let count_func_idx = module.functions_space() as u32;
+
+        let mut mbuilder = builder::from_module(module);
+        let mut count_code = vec![];
+        for func in 0..defined_funcs {
+            mbuilder =
+                mbuilder.with_global(mutable_global(ValueType::I64, Instruction::I64Const(0)));
+            count_code.extend([
+                Instruction::GetLocal(0),
+                Instruction::I32Const((imported_funcs + func) as i32),
+                Instruction::I32Eq,
+                Instruction::If(BlockType::NoResult),
+                Instruction::GetGlobal(first_counter + func),
+                Instruction::Return,
+                Instruction::End,
+            ]);
+        }
+        count_code.extend([Instruction::I64Const(0), Instruction::End]);
+        mbuilder.push_function(
+            builder::function()
+                .signature()
+                .with_param(ValueType::I32)
+                .with_result(ValueType::I64)
+                .build()
+                .body()
+                .with_instructions(elements::Instructions::new(count_code))
+                .build()
+                .build(),
+        );
+        mbuilder.push_export(
+            builder::export()
+                .field(COVERAGE_EXPORT)
+                .internal()
+                .func(count_func_idx)
+                .build(),
+        );
+        let mut module = mbuilder.build();
+
+        if let Some(code_section) = module.code_section_mut() {
+            let bodies = code_section.bodies_mut();
+            for (func, body) in bodies[..defined_funcs as usize].iter_mut().enumerate() {
+                let counter = first_counter + func as u32;
+                body.code_mut().elements_mut().splice(
+                    0..0,
+                    [
+                        Instruction::GetGlobal(counter),
+                        Instruction::I64Const(1),
+                        Instruction::I64Add,
+                        Instruction::SetGlobal(counter),
+                    ],
+                );
+            }
+        }
+        Ok(module)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{runtime::ZenRuntime, types::ZenValue};
+    use crate::gas_metering::ConstantCostRules;
+
+    /// Records its name in a custom section, to observe the order passes ran in
+    struct TagPass(&'static str);
+
+    impl ModulePass for TagPass {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn run(&self, mut module: elements::Module) -> Result<elements::Module, String> {
+            module
+                .sections_mut()
+                .push(elements::Section::Custom(elements::CustomSection::new(
+                    "pass_order".to_string(),
+                    self.0.as_bytes().to_vec(),
+                )));
+            Ok(module)
+        }
+    }
+
+    fn has_gas_export(module: &elements::Module) -> bool {
+        module.export_section().is_some_and(|export_section| {
+            export_section
+                .entries()
+                .iter()
+                .any(|export| export.field() == "__instrumented_use_gas")
+        })
+    }
+
+    #[test]
+    fn test_pipeline_runs_passes_in_order() {
+        let wat = r#"
+            (module
+                (func $add (param $a i32) (param $b i32) (result i32)
+                    local.get $a
+                    local.get $b
+                    i32.add
+                )
+                (export "add" (func $add))
+            )
+        "#;
+        let wasm_bytes = wat::parse_str(wat).expect("Failed to parse WAT");
+        let pipeline = PassPipeline::new()
+            .with_pass(TagPass("first"))
+            .with_pass(GasMeteringPass::new(ConstantCostRules::default()))
+            .with_pass(TagPass("second"));
+        assert_eq!(
+            vec!["first", "gas_metering", "second"],
+            pipeline.pass_names()
+        );
+
+        let transformed = pipeline
+            .transform(&wasm_bytes)
+            .expect("Pipeline should succeed");
+        let module =
+            elements::Module::from_bytes(&transformed).expect("Failed to parse transformed WASM");
+        assert!(has_gas_export(&module));
+        let tags: Vec<&[u8]> = module
+            .custom_sections()
+            .filter(|section| section.name() == "pass_order")
+            .map(|section| section.payload())
+            .collect();
+        assert_eq!(vec![b"first".as_ref(), b"second".as_ref()], tags);
+    }
+
+    #[test]
+    fn test_float_policy_pass() {
+        let float_wat = r#"
+            (module
+                (func $half (param $a i32) (result i32)
+                    local.get $a
+                    f32.convert_i32_s
+                    f32.const 0.5
+                    f32.mul
+                    i32.trunc_f32_s
+                )
+                (export "half" (func $half))
+            )
+        "#;
+        let wasm_bytes = wat::parse_str(float_wat).expect("Failed to parse WAT");
+        let pipeline = PassPipeline::new().with_pass(FloatPolicyPass);
+        let err = pipeline.transform(&wasm_bytes).unwrap_err();
+        assert!(err.to_string().contains("float_policy"));
+
+        let int_wat = r#"
+            (module
+                (func $double (param $a i32) (result i32)
+                    local.get $a
+                    i32.const 2
+                    i32.mul
+                )
+                (export "double" (func $double))
+            )
+        "#;
+        let wasm_bytes = wat::parse_str(int_wat).expect("Failed to parse WAT");
+        assert!(pipeline.transform(&wasm_bytes).is_ok());
+    }
+
+    #[test]
+    fn test_pipeline_size_budget() {
+        let wat = r#"
+            (module
+                (func $abs (param $a i32) (result i32)
+                    local.get $a
+                    i32.const 0
+                    i32.lt_s
+                    if (result i32)
+                        i32.const 0
+                        local.get $a
+                        i32.sub
+                    else
+                        local.get $a
+                    end
+                )
+                (export "abs" (func $abs))
+            )
+        "#;
+        let wasm_bytes = wat::parse_str(wat).expect("Failed to parse WAT");
+        let unbounded = PassPipeline::new()
+            .with_pass(GasMeteringPass::new(ConstantCostRules::default()))
+            .transform(&wasm_bytes)
+            .expect("Pipeline should succeed");
+
+        let pipeline = PassPipeline::new()
+            .with_pass(GasMeteringPass::new(ConstantCostRules::default()))
+            .with_size_budget(unbounded.len());
+        assert!(pipeline.transform(&wasm_bytes).is_ok());
+
+        let budget = wasm_bytes.len() + 1;
+        let pipeline = PassPipeline::new()
+            .with_pass(GasMeteringPass::new(ConstantCostRules::default()))
+            .with_size_budget(budget);
+        match pipeline.transform(&wasm_bytes) {
+            Err(TransformError::SizeBudget(report)) => {
+                assert_eq!(budget, report.budget);
+                assert_eq!(wasm_bytes.len(), report.original);
+                assert_eq!(unbounded.len(), report.instrumented);
+                assert_eq!(unbounded.len() - wasm_bytes.len(), report.growth());
+                assert!(report
+                    .to_string()
+                    .contains("instrumentation pushes it over"));
+            }
+            other => panic!("Expected size budget error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_memory_limit_pass() {
+        let memory_limits = |wat: &str, max_pages: u32| {
+            let wasm_bytes = wat::parse_str(wat).expect("Failed to parse WAT");
+            let transformed = PassPipeline::new()
+                .with_pass(MemoryLimitPass::new(max_pages))
+                .transform(&wasm_bytes)?;
+            let module = elements::Module::from_bytes(&transformed)
+                .expect("Failed to parse transformed WASM");
+            let limits = module.memory_section().unwrap().entries()[0].limits();
+            Ok::<_, TransformError>((limits.initial(), limits.maximum()))
+        };
+
+        let unbounded = "(module (memory 1))";
+        assert_eq!((1, Some(4)), memory_limits(unbounded, 4).unwrap());
+        let bounded = "(module (memory 1 2))";
+        assert_eq!((1, Some(2)), memory_limits(bounded, 4).unwrap());
+        assert_eq!((1, Some(1)), memory_limits(bounded, 1).unwrap());
+
+        let err = memory_limits("(module (memory 3))", 2).unwrap_err();
+        assert!(err.to_string().contains("memory_limit"));
+    }
+
+    #[test]
+    fn test_stack_limit_pass() {
+        let wat = r#"
+            (module
+                (func $depth (param $n i32) (result i32)
+                    local.get $n
+                    i32.eqz
+                    if (result i32)
+                        i32.const 0
+                    else
+                        local.get $n
+                        i32.const 1
+                        i32.sub
+                        call $depth
+                        i32.const 1
+                        i32.add
+                    end
+                )
+                (export "depth" (func $depth))
+            )
+        "#;
+        let wasm_bytes = wat::parse_str(wat).expect("Failed to parse WAT");
+        let transformed = PassPipeline::new()
+            .with_pass(StackLimitPass::new(10))
+            .with_pass(GasMeteringPass::new(ConstantCostRules::default()))
+            .transform(&wasm_bytes)
+            .expect("Pipeline should succeed");
+
+        let rt = ZenRuntime::new(None);
+        let wasm_mod = rt
+            .load_module_from_bytes("stack_limit.wasm", &transformed)
+            .expect("Failed to load transformed WASM module");
+        let call_depth = |n: i32| {
+            let isolation = rt.new_isolation().expect("Failed to create isolation");
+            let inst = wasm_mod
+                .new_instance(isolation, 1000000)
+                .expect("Failed to create WASM instance");
+            inst.call_wasm_func("depth", &[ZenValue::ZenI32Value(n)])
+        };
+        let results = call_depth(10).expect("Calls within the limit should succeed");
+        assert_eq!("10", results[0].to_string());
+        assert!(call_depth(11).is_err());
+    }
+
+    #[test]
+    fn test_coverage_pass() {
+        let wat = r#"
+            (module
+                (import "env" "log" (func $log (param i32)))
+                (func $double (param $a i32) (result i32)
+                    local.get $a
+                    i32.const 2
+                    i32.mul
+                )
+                (func $quadruple (param $a i32) (result i32)
+                    local.get $a
+                    call $double
+                    call $double
+                )
+                (func $unused)
+                (export "quadruple" (func $quadruple))
+            )
+        "#;
+        let wasm_bytes = wat::parse_str(wat).expect("Failed to parse WAT");
+        let transformed = PassPipeline::new()
+            .with_pass(CoveragePass)
+            .transform(&wasm_bytes)
+            .expect("Pipeline should succeed");
+        let module =
+            elements::Module::from_bytes(&transformed).expect("Failed to parse transformed WASM");
+        assert_eq!(3, module.global_section().unwrap().entries().len());
+        assert!(module
+            .export_section()
+            .unwrap()
+            .entries()
+            .iter()
+            .any(|export| export.field() == COVERAGE_EXPORT
+                && matches!(export.internal(), elements::Internal::Function(4))));
+        let quadruple = &module.code_section().unwrap().bodies()[1];
+        assert_eq!(
+            &[
+                Instruction::GetGlobal(1),
+                Instruction::I64Const(1),
+                Instruction::I64Add,
+                Instruction::SetGlobal(1),
+            ],
+            &quadruple.code().elements()[..4]
+        );
+    }
+}
@@ -0,0 +1,216 @@
+// Copyright (C) 2021-2025 the DTVM authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! A [`Rules`] implementation loaded from a JSON or TOML cost table.
+//!
+//! Chain operators can tune costs without recompiling. A TOML table looks like:
+//!
+//! ```toml
+//! memory_grow_cost = 8192
+//! call_per_local_cost = 1
+//! # Cost of classes missing from [instruction_costs], omit it to forbid them
+//! default_cost = 1
+//!
+//! [instruction_costs]
+//! arithmetic = 1
+//! multiplication = 3
+//! division = 8
+//! call_indirect = 20
+//! ```
+//!
+//! Keys of `instruction_costs` are [`InstructionClass`] names in snake case. Unknown classes and
+//! unknown top level keys are rejected. [`TableCostRules::to_toml`] writes a table in this
+//! format, e.g. for the output of the `calibrate` example.
+
+use super::gas_inject::{MemoryGrowCost, Rules};
+use super::instruction_class::{instruction_class, InstructionClass};
+use parity_wasm::elements::Instruction;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::num::NonZeroU32;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CostTableError {
+    #[error("Invalid JSON cost table: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Invalid TOML cost table: {0}")]
+    Toml(#[from] toml::de::Error),
+
+    #[error("Failed to write TOML cost table: {0}")]
+    TomlWrite(#[from] toml::ser::Error),
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct CostTable {
+    #[serde(default)]
+    memory_grow_cost: u32,
+    #[serde(default)]
+    call_per_local_cost: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default_cost: Option<u32>,
+    #[serde(default)]
+    instruction_costs: BTreeMap<InstructionClass, u32>,
+}
+
+/// Gas rules read from a cost table keyed by [`InstructionClass`].
+///
+/// Instructions of a class without a cost, when the table has no `default_cost`, are forbidden:
+/// injection fails for modules using them.
+#[derive(Debug, Clone)]
+pub struct TableCostRules {
+    class_costs: BTreeMap<InstructionClass, u32>,
+    memory_grow_cost: u32,
+    call_per_local_cost: u32,
+}
+
+impl TableCostRules {
+    /// Create rules from per-class costs. Classes missing from `class_costs` are forbidden.
+    pub fn new(
+        class_costs: BTreeMap<InstructionClass, u32>,
+        memory_grow_cost: u32,
+        call_per_local_cost: u32,
+    ) -> Self {
+        Self {
+            class_costs,
+            memory_grow_cost,
+            call_per_local_cost,
+        }
+    }
+
+    /// Load rules from a JSON cost table.
+    pub fn from_json(table: &str) -> Result<Self, CostTableError> {
+        Ok(Self::from_table(serde_json::from_str(table)?))
+    }
+
+    /// Load rules from a TOML cost table.
+    pub fn from_toml(table: &str) -> Result<Self, CostTableError> {
+        Ok(Self::from_table(toml::from_str(table)?))
+    }
+
+    fn from_table(table: CostTable) -> Self {
+        let mut class_costs = table.instruction_costs;
+        if let Some(default_cost) = table.default_cost {
+            for class in InstructionClass::ALL {
+                class_costs.entry(class).or_insert(default_cost);
+            }
+        }
+        Self {
+            class_costs,
+            memory_grow_cost: table.memory_grow_cost,
+            call_per_local_cost: table.call_per_local_cost,
+        }
+    }
+
+    /// Write the rules as a TOML cost table [`TableCostRules::from_toml`] loads back.
+    pub fn to_toml(&self) -> Result<String, CostTableError> {
+        Ok(toml::to_string(&CostTable {
+            memory_grow_cost: self.memory_grow_cost,
+            call_per_local_cost: self.call_per_local_cost,
+            default_cost: None,
+            instruction_costs: self.class_costs.clone(),
+        })?)
+    }
+
+    /// Returns the cost of an instruction class, `None` if the class is forbidden.
+    pub fn class_cost(&self, class: InstructionClass) -> Option<u32> {
+        self.class_costs.get(&class).copied()
+    }
+}
+
+impl Rules for TableCostRules {
+    fn instruction_cost(&self, instruction: &Instruction) -> Option<u32> {
+        instruction_class(instruction).and_then(|class| self.class_cost(class))
+    }
+
+    fn memory_grow_cost(&self) -> MemoryGrowCost {
+        NonZeroU32::new(self.memory_grow_cost).map_or(MemoryGrowCost::Free, MemoryGrowCost::Linear)
+    }
+
+    fn call_per_local_cost(&self) -> u32 {
+        self.call_per_local_cost
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gas_metering::GasMeter;
+
+    #[test]
+    fn test_table_cost_rules_from_toml() {
+        let rules = TableCostRules::from_toml(
+            r#"
+                memory_grow_cost = 8192
+                call_per_local_cost = 2
+                default_cost = 1
+
+                [instruction_costs]
+                multiplication = 3
+                call_indirect = 20
+            "#,
+        )
+        .expect("Failed to load TOML cost table");
+        assert_eq!(Some(3), rules.instruction_cost(&Instruction::I32Mul));
+        assert_eq!(Some(1), rules.instruction_cost(&Instruction::I32Add));
+        assert_eq!(Some(20), rules.class_cost(InstructionClass::CallIndirect));
+        assert_eq!(
+            MemoryGrowCost::Linear(NonZeroU32::new(8192).unwrap()),
+            rules.memory_grow_cost()
+        );
+        assert_eq!(2, rules.call_per_local_cost());
+    }
+
+    #[test]
+    fn test_table_cost_rules_from_json() {
+        let rules = TableCostRules::from_json(
+            r#"{"instruction_costs": {"const": 1, "local": 1, "arithmetic": 2}}"#,
+        )
+        .expect("Failed to load JSON cost table");
+        assert_eq!(Some(2), rules.instruction_cost(&Instruction::I64Add));
+        assert_eq!(None, rules.instruction_cost(&Instruction::I32Mul));
+        assert_eq!(MemoryGrowCost::Free, rules.memory_grow_cost());
+
+        // Classes without a cost are forbidden
+        let add = wat::parse_str(
+            "(module (func (param i32) (result i32) local.get 0 i32.const 1 i32.add))",
+        )
+        .expect("Failed to parse WAT");
+        assert!(GasMeter::transform_with_rules(&add, rules.clone()).is_ok());
+        let mul = wat::parse_str(
+            "(module (func (param i32) (result i32) local.get 0 i32.const 2 i32.mul))",
+        )
+        .expect("Failed to parse WAT");
+        assert!(GasMeter::transform_with_rules(&mul, rules).is_err());
+    }
+
+    #[test]
+    fn test_table_cost_rules_toml_round_trip() {
+        let class_costs = BTreeMap::from([
+            (InstructionClass::Arithmetic, 1),
+            (InstructionClass::Division, 8),
+            (InstructionClass::MemoryLoad, 3),
+        ]);
+        let rules = TableCostRules::new(class_costs, 0, 1);
+        let table = rules.to_toml().expect("Failed to write TOML cost table");
+        assert!(table.contains("memory_load = 3"));
+
+        let loaded = TableCostRules::from_toml(&table).expect("Failed to load written table");
+        for class in InstructionClass::ALL {
+            assert_eq!(rules.class_cost(class), loaded.class_cost(class));
+        }
+        assert_eq!(MemoryGrowCost::Free, loaded.memory_grow_cost());
+        assert_eq!(1, loaded.call_per_local_cost());
+    }
+
+    #[test]
+    fn test_table_cost_rules_rejects_unknown_keys() {
+        let err = TableCostRules::from_toml("[instruction_costs]\n\"i32.add\" = 1\n").unwrap_err();
+        assert!(err.to_string().contains("i32.add"));
+
+        let err = TableCostRules::from_json(r#"{"memory_grow": 1}"#).unwrap_err();
+        assert!(err.to_string().contains("memory_grow"));
+    }
+}
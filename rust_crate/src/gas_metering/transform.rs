@@ -1,8 +1,10 @@
 // Copyright (C) 2021-2025 the DTVM authors. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use super::gas_inject::{inject, ConstantCostRules, Rules};
-use parity_wasm::{elements, serialize};
+use super::gas_inject::{ConstantCostRules, GasFunction, Rules};
+use super::pass::{GasMeteringPass, PassPipeline};
+use parity_wasm::elements;
+use std::fmt;
 use thiserror::Error;
 
 /// Simple gas meter for WASM modules
@@ -16,6 +18,75 @@ pub enum TransformError {
 
     #[error("Failed to serialize WASM: {0}")]
     Serialize(elements::Error),
+
+    #[error("Pass {0} failed: {1}")]
+    Pass(String, String),
+
+    #[error("Instrumented module exceeds the code size budget: {0}")]
+    SizeBudget(SizeReport),
+}
+
+/// Size of a module before and after instrumentation, compared to a code size budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeReport {
+    pub budget: usize,
+    pub original: usize,
+    pub instrumented: usize,
+}
+
+impl SizeReport {
+    /// Bytes added by instrumentation.
+    pub fn growth(&self) -> usize {
+        self.instrumented.saturating_sub(self.original)
+    }
+
+    pub fn within_budget(&self) -> bool {
+        self.instrumented <= self.budget
+    }
+}
+
+impl fmt::Display for SizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes for a budget of {} bytes ({} bytes of original code, {} bytes added by \
+             instrumentation)",
+            self.instrumented,
+            self.budget,
+            self.original,
+            self.growth()
+        )?;
+        if self.original > self.budget {
+            write!(
+                f,
+                "; the original module alone is over budget, shrink the contract"
+            )
+        } else {
+            write!(
+                f,
+                "; instrumentation pushes it over budget, shrink the contract (fewer branches \
+                 and functions mean fewer gas charges) or raise the budget"
+            )
+        }
+    }
+}
+
+/// Compare the size of an `instrumented` module with the `original` one and a size `budget`.
+pub fn check_size_budget(
+    original: &[u8],
+    instrumented: &[u8],
+    budget: usize,
+) -> Result<SizeReport, TransformError> {
+    let report = SizeReport {
+        budget,
+        original: original.len(),
+        instrumented: instrumented.len(),
+    };
+    if report.within_budget() {
+        Ok(report)
+    } else {
+        Err(TransformError::SizeBudget(report))
+    }
 }
 pub struct GasMeter;
 
@@ -34,16 +105,26 @@ impl GasMeter {
     }
 
     /// Transform WASM with custom gas rules
-    pub fn transform_with_rules<T: Rules>(
+    pub fn transform_with_rules<T: Rules + 'static>(
         input_wasm: &[u8],
         gas_rules: T,
     ) -> Result<Vec<u8>, TransformError> {
-        let module = elements::Module::from_bytes(input_wasm).map_err(TransformError::Parse)?;
-
-        let injected_module = inject(module, &gas_rules)
-            .map_err(|err| TransformError::Inject(format!("{:?}", err)))?;
+        Self::transform_with_gas_function(input_wasm, gas_rules, &GasFunction::Local)
+    }
 
-        serialize(injected_module).map_err(TransformError::Serialize)
+    /// Transform WASM with custom gas rules, charging gas through `gas_function`
+    pub fn transform_with_gas_function<T: Rules + 'static>(
+        input_wasm: &[u8],
+        gas_rules: T,
+        gas_function: &GasFunction,
+    ) -> Result<Vec<u8>, TransformError> {
+        PassPipeline::new()
+            .with_pass(GasMeteringPass::new(gas_rules).with_gas_function(gas_function.clone()))
+            .transform(input_wasm)
+            .map_err(|err| match err {
+                TransformError::Pass(_, reason) => TransformError::Inject(reason),
+                err => err,
+            })
     }
 }
 
@@ -51,7 +132,7 @@ impl GasMeter {
 mod tests {
     use super::*;
     use crate::core::{runtime::ZenRuntime, types::ZenValue};
-    use parity_wasm::elements;
+    use parity_wasm::{elements, serialize};
 
     const INSTRUMENTED_USE_GAS: &str = "__instrumented_use_gas";
 
@@ -288,4 +369,459 @@ mod tests {
             },
         );
     }
+
+    #[test]
+    fn test_transform_metering_exempt_function() {
+        let wat = r#"
+            (module
+                (func $sum (param $n i32) (result i32)
+                    (local $acc i32)
+                    (block $done
+                        (loop $next
+                            (br_if $done (i32.eqz (local.get $n)))
+                            (local.set $acc (i32.add (local.get $acc) (local.get $n)))
+                            (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                            (br $next)
+                        )
+                    )
+                    local.get $acc
+                )
+                (export "__dtvm_free_sum" (func $sum))
+                (export "sum" (func $sum))
+            )
+        "#;
+
+        let wasm_bytes = wat::parse_str(wat).expect("Failed to parse WAT");
+        let module = elements::Module::from_bytes(&wasm_bytes).expect("Failed to parse WASM");
+        let audited_code = elements::serialize(module.code_section().unwrap().bodies()[0].clone())
+            .expect("Failed to serialize function body");
+        let rules = ConstantCostRules::new(1, 0, 1).with_exempt_function(
+            "__dtvm_free_sum",
+            audited_code,
+            7,
+        );
+        let transformed = GasMeter::transform_with_rules(&wasm_bytes, rules)
+            .expect("Transform with rules should succeed");
+
+        // 1) The exempt function is charged exactly once, at its entry
+        assert_gas_export_and_calls(&transformed);
+        let module =
+            elements::Module::from_bytes(&transformed).expect("Failed to parse transformed WASM");
+        let body = &module.code_section().unwrap().bodies()[0];
+        let gas_calls = body
+            .code()
+            .elements()
+            .iter()
+            .filter(|instruction| matches!(instruction, elements::Instruction::Call(_)))
+            .count();
+        assert_eq!(1, gas_calls);
+
+        // 2) The flat cost doesn't depend on the loop iterations
+        for n in [1, 10] {
+            execute_and_assert(
+                &transformed,
+                1000,
+                "__dtvm_free_sum",
+                &[ZenValue::ZenI32Value(n)],
+                |values| {
+                    assert!(
+                        matches!(values[0], ZenValue::ZenI32Value(v) if v == n * (n + 1) / 2),
+                        "Unexpected sum {}",
+                        values[0]
+                    );
+                },
+                |left| {
+                    assert_eq!(left, 993, "Expected gas left 993, got {}", left);
+                },
+            );
+        }
+
+        // 3) A function reusing the audited name with other code is metered as usual
+        let rules =
+            ConstantCostRules::new(1, 0, 1).with_exempt_function("__dtvm_free_sum", vec![0], 7);
+        let transformed = GasMeter::transform_with_rules(&wasm_bytes, rules)
+            .expect("Transform with rules should succeed");
+        let module =
+            elements::Module::from_bytes(&transformed).expect("Failed to parse transformed WASM");
+        let body = &module.code_section().unwrap().bodies()[0];
+        let gas_calls = body
+            .code()
+            .elements()
+            .iter()
+            .filter(|instruction| matches!(instruction, elements::Instruction::Call(_)))
+            .count();
+        assert!(gas_calls > 1);
+
+        // 4) A flat cost the gas function can't take fails the transform
+        let audited_code = elements::serialize(
+            elements::Module::from_bytes(&wasm_bytes)
+                .unwrap()
+                .code_section()
+                .unwrap()
+                .bodies()[0]
+                .clone(),
+        )
+        .unwrap();
+        let rules = ConstantCostRules::new(1, 0, 1).with_exempt_function(
+            "__dtvm_free_sum",
+            audited_code.clone(),
+            u64::MAX,
+        );
+        assert!(matches!(
+            GasMeter::transform_with_rules(&wasm_bytes, rules),
+            Err(TransformError::Inject(reason)) if reason.contains("exceeds i64::MAX")
+        ));
+
+        // 5) Names without the exempt prefix are metered even with audited code
+        let rules = ConstantCostRules::new(1, 0, 1).with_exempt_function("sum", audited_code, 7);
+        let transformed = GasMeter::transform_with_rules(&wasm_bytes, rules)
+            .expect("Transform with rules should succeed");
+        let module =
+            elements::Module::from_bytes(&transformed).expect("Failed to parse transformed WASM");
+        let body = &module.code_section().unwrap().bodies()[0];
+        let gas_calls = body
+            .code()
+            .elements()
+            .iter()
+            .filter(|instruction| matches!(instruction, elements::Instruction::Call(_)))
+            .count();
+        assert!(gas_calls > 1);
+    }
+
+    #[test]
+    fn test_transform_multi_function_round_trip() {
+        let wat = r#"
+            (module
+                (func $square (param $a i32) (result i32)
+                    local.get $a
+                    local.get $a
+                    i32.mul
+                )
+                (func $inc (param $a i32) (result i32)
+                    local.get $a
+                    i32.const 1
+                    i32.add
+                )
+                (func $square_inc (param $a i32) (result i32)
+                    local.get $a
+                    call $square
+                    call $inc
+                )
+                (export "square_inc" (func $square_inc))
+            )
+        "#;
+
+        let wasm_bytes = wat::parse_str(wat).expect("Failed to parse WAT");
+        let transformed =
+            GasMeter::transform_default(&wasm_bytes).expect("Transform should succeed");
+
+        // 1) All bodies, including the gas function, live in a single code section
+        let module =
+            elements::Module::from_bytes(&transformed).expect("Failed to parse transformed WASM");
+        let code_sections = module
+            .sections()
+            .iter()
+            .filter(|section| matches!(section, elements::Section::Code(_)))
+            .count();
+        assert_eq!(1, code_sections);
+        assert_eq!(4, module.code_section().unwrap().bodies().len());
+        assert_eq!(4, module.function_section().unwrap().entries().len());
+
+        // 2) Re-serializing the parsed module is stable
+        let reserialized = serialize(module).expect("Failed to serialize WASM");
+        assert_eq!(transformed, reserialized);
+
+        // 3) Calls across functions still work after instrumentation
+        execute_and_assert(
+            &transformed,
+            1000,
+            "square_inc",
+            &[ZenValue::ZenI32Value(3)],
+            |values| {
+                assert!(
+                    matches!(values[0], ZenValue::ZenI32Value(10)),
+                    "Expected return 10, got {}",
+                    values[0]
+                );
+            },
+            |left| {
+                assert_eq!(left, 991, "Expected gas left 991, got {}", left);
+            },
+        );
+    }
+
+    #[test]
+    fn test_transform_preserves_all_sections() {
+        let wat = r#"
+            (module
+                (import "env" "get_host_number" (func $host (param i32 i32) (result i32)))
+                (table 2 funcref)
+                (memory 1 4)
+                (global $counter (mut i32) (i32.const 7))
+                (global $limit i64 (i64.const 1024))
+                (func $one (result i32) i32.const 1)
+                (func $two (result i32) i32.const 2)
+                (func $pick (param $i i32) (result i32)
+                    global.get $counter
+                    local.get $i
+                    call_indirect (result i32)
+                    i32.add
+                )
+                (elem (i32.const 0) $one $two)
+                (data (i32.const 16) "payload")
+                (export "pick" (func $pick))
+                (export "memory" (memory 0))
+                (@custom "dtvm_meta" "opaque bytes")
+            )
+        "#;
+
+        let wasm_bytes = wat::parse_str(wat).expect("Failed to parse WAT");
+        let original = elements::Module::from_bytes(&wasm_bytes).expect("Failed to parse WASM");
+        let transformed =
+            GasMeter::transform_default(&wasm_bytes).expect("Transform should succeed");
+        let module =
+            elements::Module::from_bytes(&transformed).expect("Failed to parse transformed WASM");
+
+        assert_eq!(original.import_section(), module.import_section());
+        assert_eq!(original.table_section(), module.table_section());
+        assert_eq!(original.memory_section(), module.memory_section());
+        assert_eq!(original.global_section(), module.global_section());
+        assert_eq!(original.elements_section(), module.elements_section());
+        assert_eq!(original.data_section(), module.data_section());
+        // The name section is updated with the injected functions, see
+        // test_transform_updates_name_section
+        let custom_sections = |module: &elements::Module| -> Vec<(String, Vec<u8>)> {
+            module
+                .custom_sections()
+                .filter(|section| section.name() != "name")
+                .map(|section| (section.name().to_string(), section.payload().to_vec()))
+                .collect()
+        };
+        assert_eq!(custom_sections(&original), custom_sections(&module));
+        assert!(custom_sections(&module)
+            .iter()
+            .any(|(name, payload)| name == "dtvm_meta" && payload == b"opaque bytes"));
+
+        // Only the injected gas function and its export are added
+        assert_eq!(
+            original.code_section().unwrap().bodies().len() + 1,
+            module.code_section().unwrap().bodies().len()
+        );
+        assert_eq!(
+            original.export_section().unwrap().entries().len() + 1,
+            module.export_section().unwrap().entries().len()
+        );
+    }
+
+    #[test]
+    fn test_transform_updates_name_section() {
+        let wat = r#"
+            (module
+                (func $grow_by (param $pages i32) (result i32)
+                    (local $previous i32)
+                    local.get $pages
+                    memory.grow
+                    local.tee $previous
+                )
+                (func $main (result i32)
+                    i32.const 1
+                    call $grow_by
+                )
+                (memory 1)
+                (export "main" (func $main))
+            )
+        "#;
+
+        let wasm_bytes = wat::parse_str(wat).expect("Failed to parse WAT");
+        let rules = ConstantCostRules::new(1, 100, 1);
+        let transformed =
+            GasMeter::transform_with_rules(&wasm_bytes, rules).expect("Transform should succeed");
+        let module = elements::Module::from_bytes(&transformed)
+            .expect("Failed to parse transformed WASM")
+            .parse_names()
+            .expect("Failed to parse name section");
+
+        let names_section = module.names_section().expect("name section should be kept");
+        let function_names = names_section.functions().unwrap().names();
+        assert_eq!(Some(&"grow_by".to_string()), function_names.get(0));
+        assert_eq!(Some(&"main".to_string()), function_names.get(1));
+        assert_eq!(
+            Some(&"__instrumented_use_gas".to_string()),
+            function_names.get(2)
+        );
+        assert_eq!(
+            Some(&"__instrumented_grow_counter".to_string()),
+            function_names.get(3)
+        );
+
+        let local_names = names_section.locals().unwrap().local_names();
+        let grow_by_locals = local_names.get(0).expect("grow_by local names");
+        assert_eq!(Some(&"pages".to_string()), grow_by_locals.get(0));
+        assert_eq!(Some(&"previous".to_string()), grow_by_locals.get(1));
+    }
+
+    #[test]
+    fn test_transform_sign_ext_and_bulk_memory() {
+        let wat = r#"
+            (module
+                (memory 1)
+                (func $sign_ext (param $a i32) (result i64)
+                    local.get $a
+                    i32.extend8_s
+                    i64.extend_i32_s
+                    i64.extend32_s
+                )
+                (func $copy (param $dst i32) (param $src i32) (param $len i32)
+                    local.get $dst
+                    i32.const 0
+                    local.get $len
+                    memory.fill
+                    local.get $dst
+                    local.get $src
+                    local.get $len
+                    memory.copy
+                )
+                (export "sign_ext" (func $sign_ext))
+                (export "copy" (func $copy))
+            )
+        "#;
+
+        let wasm_bytes = wat::parse_str(wat).expect("Failed to parse WAT");
+        let transformed =
+            GasMeter::transform_default(&wasm_bytes).expect("Transform should succeed");
+        let module =
+            elements::Module::from_bytes(&transformed).expect("Failed to parse transformed WASM");
+
+        let bodies = module.code_section().unwrap().bodies();
+        // Each function is a single metered block charging one gas per instruction
+        assert_eq!(
+            &elements::Instruction::I64Const(4),
+            &bodies[0].code().elements()[0]
+        );
+        assert_eq!(
+            &elements::Instruction::I64Const(8),
+            &bodies[1].code().elements()[0]
+        );
+        assert!(bodies[1]
+            .code()
+            .elements()
+            .iter()
+            .any(|instruction| matches!(
+                instruction,
+                elements::Instruction::Bulk(elements::BulkInstruction::MemoryCopy)
+            )));
+    }
+
+    #[test]
+    fn test_transform_simd() {
+        let wat = r#"
+            (module
+                (func $sum_lanes (param $a v128) (param $b v128) (result i32)
+                    local.get $a
+                    local.get $b
+                    i32x4.add
+                    v128.const i32x4 1 2 3 4
+                    i32x4.mul
+                    i32x4.extract_lane 3
+                )
+                (export "sum_lanes" (func $sum_lanes))
+            )
+        "#;
+
+        let wasm_bytes = wat::parse_str(wat).expect("Failed to parse WAT");
+        let transformed =
+            GasMeter::transform_default(&wasm_bytes).expect("Transform should succeed");
+        let module =
+            elements::Module::from_bytes(&transformed).expect("Failed to parse transformed WASM");
+        let body = &module.code_section().unwrap().bodies()[0];
+        assert_eq!(
+            &elements::Instruction::I64Const(6),
+            &body.code().elements()[0]
+        );
+    }
+
+    #[test]
+    fn test_transform_imported_gas_function() {
+        let wat = r#"
+            (module
+                (import "env" "get_host_number" (func $host (param i32 i32) (result i32)))
+                (table 2 funcref)
+                (func $one (result i32) i32.const 1)
+                (func $two (result i32)
+                    call $one
+                    i32.const 1
+                    i32.add
+                )
+                (func $init)
+                (elem (i32.const 0) $one $two)
+                (start $init)
+                (export "two" (func $two))
+                (export "host" (func $host))
+            )
+        "#;
+
+        let wasm_bytes = wat::parse_str(wat).expect("Failed to parse WAT");
+        let gas_function = GasFunction::Imported {
+            module: "env".to_string(),
+            name: "gas".to_string(),
+        };
+        let transformed = GasMeter::transform_with_gas_function(
+            &wasm_bytes,
+            ConstantCostRules::default(),
+            &gas_function,
+        )
+        .expect("Transform should succeed");
+        let module = elements::Module::from_bytes(&transformed)
+            .expect("Failed to parse transformed WASM")
+            .parse_names()
+            .expect("Failed to parse name section");
+
+        // env.gas is imported after the existing import, as function 1
+        let imports: Vec<(&str, &str)> = module
+            .import_section()
+            .unwrap()
+            .entries()
+            .iter()
+            .map(|import| (import.module(), import.field()))
+            .collect();
+        assert_eq!(vec![("env", "get_host_number"), ("env", "gas")], imports);
+        assert_eq!(3, module.code_section().unwrap().bodies().len());
+
+        // $one, $two and $init moved from 1, 2, 3 to 2, 3, 4
+        let exports: Vec<(&str, &elements::Internal)> = module
+            .export_section()
+            .unwrap()
+            .entries()
+            .iter()
+            .map(|export| (export.field(), export.internal()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("two", &elements::Internal::Function(3)),
+                ("host", &elements::Internal::Function(0)),
+            ],
+            exports
+        );
+        assert_eq!(
+            &[2, 3],
+            module.elements_section().unwrap().entries()[0].members()
+        );
+        assert_eq!(Some(4), module.start_section());
+
+        let two_body = module.code_section().unwrap().bodies()[1].code().elements();
+        assert_eq!(
+            &[
+                elements::Instruction::I64Const(3),
+                elements::Instruction::Call(1),
+                elements::Instruction::Call(2),
+            ],
+            &two_body[..3]
+        );
+
+        let function_names = module.names_section().unwrap().functions().unwrap().names();
+        assert_eq!(Some(&"host".to_string()), function_names.get(0));
+        assert_eq!(Some(&"gas".to_string()), function_names.get(1));
+        assert_eq!(Some(&"one".to_string()), function_names.get(2));
+        assert_eq!(Some(&"init".to_string()), function_names.get(4));
+    }
 }
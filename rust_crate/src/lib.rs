@@ -3,4 +3,5 @@
 
 pub mod core;
 pub mod gas_metering;
+pub mod prelude;
 pub mod tests;
@@ -0,0 +1,20 @@
+// Copyright (C) 2021-2025 the DTVM authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Commonly used types, so programs can start with `use dtvmcore_rust::prelude::*;`.
+
+pub use crate::core::{
+    abi_version::{CURRENT_HOST_ABI_VERSION, HOST_ABI_VERSION_SECTION},
+    compat::{analyze_contract_compat, CompatReport, WasmProposal},
+    config::ZenRuntimeMode,
+    host_imports::HostImportUsage,
+    host_module::{HostFunctionError, ZenHostFuncDesc, ZenHostFuncSignature, ZenHostModule},
+    instance::{ZenBorrowError, ZenCallError, ZenInstance},
+    instance_pool::{InstancePool, PooledInstance},
+    isolation::ZenIsolation,
+    metrics::{HostCallMetrics, HostCallStats},
+    r#extern::ZenInstanceExtern,
+    runtime::{ZenModule, ZenRuntime, ZenRuntimeResources},
+    types::{ZenValue, ZenValueError, ZenValueType},
+};
+pub use crate::gas_metering::{ConstantCostRules, GasMeter, Rules};
@@ -0,0 +1,86 @@
+// Copyright (C) 2021-2025 the DTVM authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Snapshot tests of the gas charged per fixture call.
+//!
+//! Each fixture under `./example` has a checked-in `./example/gas_snapshots/<fixture>.snap`
+//! with one `func(args): gas_used` line per call. Any change of the metering algorithm or the
+//! default gas rules shows up as a diff of these files. Run the tests with
+//! `DTVM_UPDATE_GAS_SNAPSHOTS=1` to rewrite the snapshots after an intended change.
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::core::{runtime::ZenRuntime, types::ZenValue};
+    use crate::gas_metering::GasMeter;
+
+    const SNAPSHOT_DIR: &str = "./example/gas_snapshots";
+    const UPDATE_ENV: &str = "DTVM_UPDATE_GAS_SNAPSHOTS";
+    const GAS_LIMIT: u64 = 100000000;
+
+    /// Instrument the fixture with the default rules and record the gas used by each call
+    fn record_gas_used(fixture: &str, calls: &[(&str, Vec<ZenValue>)]) -> String {
+        let wasm_bytes = wat::parse_file(format!("./example/{fixture}.wast"))
+            .expect("Failed to parse fixture WAST");
+        let gas_bytes = GasMeter::transform_default(&wasm_bytes)
+            .expect("Failed to compile with gas instrumentation");
+
+        let rt = ZenRuntime::new(None);
+        let wasm_mod = rt
+            .load_module_from_bytes(fixture, &gas_bytes)
+            .expect("Failed to load WASM module");
+
+        let mut snapshot = String::new();
+        for (func_name, args) in calls {
+            // fresh instance per call so gas is measured from a full limit
+            let isolation = rt.new_isolation().expect("Failed to create isolation");
+            let inst = wasm_mod
+                .new_instance(isolation, GAS_LIMIT)
+                .expect("Failed to create WASM instance");
+            inst.call_wasm_func(func_name, args)
+                .expect("Failed to call fixture function");
+            let args_str: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            snapshot.push_str(&format!(
+                "{func_name}({}): {}\n",
+                args_str.join(", "),
+                GAS_LIMIT - inst.get_gas_left()
+            ));
+        }
+        snapshot
+    }
+
+    fn assert_gas_snapshot(fixture: &str, actual: &str) {
+        let snapshot_path = format!("{SNAPSHOT_DIR}/{fixture}.snap");
+        if std::env::var_os(UPDATE_ENV).is_some() {
+            fs::write(&snapshot_path, actual).expect("Failed to write gas snapshot");
+            return;
+        }
+        let expected = fs::read_to_string(&snapshot_path).unwrap_or_default();
+        if expected == actual {
+            return;
+        }
+        panic!(
+            "gas snapshot {snapshot_path} changed (rerun with {UPDATE_ENV}=1 to accept):\n\
+             --- expected\n{expected}+++ actual\n{actual}"
+        );
+    }
+
+    #[test]
+    fn test_gas_snapshot_fib() {
+        let actual = record_gas_used(
+            "fib",
+            &[
+                ("fib", vec![ZenValue::ZenI32Value(5)]),
+                ("fib", vec![ZenValue::ZenI32Value(10)]),
+            ],
+        );
+        assert_gas_snapshot("fib", &actual);
+    }
+
+    #[test]
+    fn test_gas_snapshot_infinite() {
+        let actual = record_gas_used("infinite", &[("test_then_infinite", vec![])]);
+        assert_gas_snapshot("infinite", &actual);
+    }
+}
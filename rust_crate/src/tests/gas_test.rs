@@ -5,9 +5,16 @@
 mod tests {
     use std::fs;
     use std::rc::Rc;
+    use std::time::Duration;
 
-    use crate::core::{instance::ZenInstance, runtime::ZenRuntime, types::ZenValue};
-    use crate::gas_metering::GasMeter;
+    use crate::core::{
+        config::ZenRuntimeMode,
+        gas_host::gas_host_funcs,
+        instance::{ZenCallError, ZenInstance},
+        runtime::ZenRuntime,
+        types::ZenValue,
+    };
+    use crate::gas_metering::{ConstantCostRules, GasFunction, GasMeter};
 
     /// Helper function to compile WAST to WASM if needed
     fn get_wasm_bytes(wast_path: &str, wasm_path: &str) -> Result<Vec<u8>, String> {
@@ -43,7 +50,13 @@ mod tests {
 
     /// Helper function to set up runtime with gas host module and load WASM
     fn setup_gas_test(gas_limit: u64) -> Result<(Rc<ZenRuntime>, Rc<ZenInstance<i64>>), String> {
-        let rt = create_runtime();
+        setup_gas_test_in(create_runtime(), gas_limit)
+    }
+
+    fn setup_gas_test_in(
+        rt: Rc<ZenRuntime>,
+        gas_limit: u64,
+    ) -> Result<(Rc<ZenRuntime>, Rc<ZenInstance<i64>>), String> {
         // Load the WASM file
         let wasm_bytes = get_wasm_bytes("./example/infinite.wast", "./example/infinite.wasm")?;
 
@@ -93,6 +106,10 @@ mod tests {
                     err
                 );
                 assert_eq!(0, inst.get_gas_left(), "Gas left: {}", inst.get_gas_left());
+                assert_eq!(
+                    ZenCallError::OutOfGas,
+                    ZenCallError::from_error_code(inst.error_code(), err)
+                );
             }
         }
     }
@@ -134,4 +151,85 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_imported_gas_function_and_gas_left() {
+        let wat = r#"
+            (module
+                (import "env" "get_gas_left" (func $get_gas_left (result i64)))
+                (func (export "test") (result i64)
+                    i32.const 1
+                    drop
+                    call $get_gas_left
+                )
+            )
+        "#;
+        let wasm_bytes = wat::parse_str(wat).expect("Failed to parse WAT");
+        let gas_function = GasFunction::Imported {
+            module: "env".to_string(),
+            name: "gas".to_string(),
+        };
+        let gas_bytes = GasMeter::transform_with_gas_function(
+            &wasm_bytes,
+            ConstantCostRules::default(),
+            &gas_function,
+        )
+        .expect("Failed to instrument WASM");
+
+        let rt = create_runtime();
+        rt.create_host_module("env", gas_host_funcs::<i64>().iter(), true)
+            .expect("Failed to create gas host module");
+        let wasm_mod = rt
+            .load_module_from_bytes("gas_left.wasm", &gas_bytes)
+            .expect("Failed to load WASM module");
+
+        // i32.const, drop and call are charged before get_gas_left runs
+        let inst = wasm_mod
+            .new_instance(rt.new_isolation().unwrap(), 1000)
+            .expect("Failed to create WASM instance");
+        let results = inst
+            .call_wasm_func("test", &[])
+            .expect("Failed to call test");
+        assert_eq!("997".to_string(), results[0].to_string());
+        assert_eq!(997, inst.get_gas_left());
+
+        let inst = wasm_mod
+            .new_instance(rt.new_isolation().unwrap(), 2)
+            .expect("Failed to create WASM instance");
+        assert_eq!(
+            Some(ZenCallError::OutOfGas),
+            inst.call_wasm_func_typed("test", &[]).err()
+        );
+    }
+
+    #[test]
+    fn test_call_with_timeout() {
+        let (_rt, inst) =
+            match setup_gas_test_in(ZenRuntime::new(Some(ZenRuntimeMode::Interp)), 1 << 60) {
+                Ok(result) => result,
+                Err(err) => {
+                    println!("⚠️ Skipping test - {}", err);
+                    return;
+                }
+            };
+
+        let results =
+            inst.call_wasm_func_with_timeout("test_then_infinite", &[], Duration::from_secs(10));
+        assert!(results.is_ok());
+
+        let results =
+            inst.call_wasm_func_with_timeout("infinite_with_work", &[], Duration::from_millis(100));
+        assert_eq!(Some(ZenCallError::Timeout), results.err());
+        // the interrupt does not touch the gas counter
+        assert!(inst.get_gas_left() > 0);
+
+        // the JIT never checks the interrupt, so it refuses to start the call
+        let (_rt, jit_inst) = setup_gas_test(1 << 60).unwrap();
+        let results = jit_inst.call_wasm_func_with_timeout(
+            "test_then_infinite",
+            &[],
+            Duration::from_secs(10),
+        );
+        assert!(matches!(results, Err(ZenCallError::Other(_))));
+    }
 }
@@ -1,5 +1,7 @@
 // Copyright (C) 2021-2023 the DTVM authors. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod gas_snapshot_test;
 pub mod gas_test;
 pub mod runtime_test;
+pub mod stress_test;
@@ -8,10 +8,11 @@ mod tests {
     use std::rc::Rc;
 
     use crate::core::{
-        host_module::ZenHostFuncDesc,
-        instance::ZenInstance,
+        host_module::{HostFunctionError, ZenHostFuncDesc},
+        instance::{ZenCallError, ZenInstance},
+        instance_pool::InstancePool,
         r#extern::ZenInstanceExtern,
-        runtime::ZenRuntime,
+        runtime::{ZenRuntime, ZenRuntimeResources},
         types::{ZenValue, ZenValueType},
     };
 
@@ -26,7 +27,51 @@ mod tests {
         return 100000 + memory_addr_value + a + b;
     }
 
+    // counts calls in the current call frame through the instance scratch area
+    extern "C" fn count_host_calls(wasm_inst: *mut ZenInstanceExtern, _a: i32, _b: i32) -> i32 {
+        let inst: &ZenInstance<i64> = ZenInstance::from_raw_pointer(wasm_inst);
+        let mut calls = inst.scratch::<i32>();
+        *calls += 1;
+        *calls
+    }
+
+    // returns 1 when a nested scratch borrow is reported as an error instead of panicking
+    extern "C" fn nested_scratch_borrow(
+        wasm_inst: *mut ZenInstanceExtern,
+        _a: i32,
+        _b: i32,
+    ) -> i32 {
+        let inst: &ZenInstance<i64> = ZenInstance::from_raw_pointer(wasm_inst);
+        let _outer = inst.scratch::<i32>();
+        inst.try_scratch::<i64>().is_err() as i32
+    }
+
     #[inline(never)]
+    #[cfg(feature = "experimental")]
+    extern "C" fn exposed_buffer_len(wasm_inst: *mut ZenInstanceExtern, _a: i32, _b: i32) -> i32 {
+        let inst: &ZenInstance<i64> = ZenInstance::from_raw_pointer(wasm_inst);
+        crate::core::shared_buffer::exposed_shared_buffer(inst)
+            .map_or(-1, |buffer| buffer.len() as i32)
+    }
+
+    // copies 4 bytes at `a` to `b`, like a host function reading and writing guest memory
+    #[cfg(feature = "failure-injection")]
+    extern "C" fn copy_word(wasm_inst: *mut ZenInstanceExtern, a: i32, b: i32) -> i32 {
+        let inst: &ZenInstance<i64> = ZenInstance::from_raw_pointer(wasm_inst);
+        if !inst.validate_wasm_addr(a as u32, 4) || !inst.validate_wasm_addr(b as u32, 4) {
+            inst.raise_out_of_bounds_memory_error();
+            return 0;
+        }
+        unsafe {
+            std::ptr::copy(
+                inst.get_host_memory(a as u32),
+                inst.get_host_memory(b as u32),
+                4,
+            );
+        }
+        1
+    }
+
     fn create_runtime() -> RefCell<Rc<ZenRuntime>> {
         RefCell::new(ZenRuntime::new(None))
     }
@@ -160,4 +205,708 @@ mod tests {
         assert_eq!("env", import_func0_mod);
         assert_eq!("get_host_number", import_func0_name);
     }
+
+    #[test]
+    fn test_load_module_host_abi_version_mismatch() {
+        let rt = create_runtime();
+        let rt_ref = rt.borrow();
+
+        // only the current version is accepted by default
+        let wasm_bytes = wat::parse_str(r#"(module (@custom "host_abi_version" "\02\00\00\00"))"#)
+            .expect("Failed to parse WAT");
+        let maybe_mod = rt_ref.load_module_from_bytes("abi_v2.wasm", &wasm_bytes);
+        assert!(maybe_mod.is_err());
+        println!("load module error: {}", maybe_mod.err().unwrap());
+
+        rt_ref.set_supported_host_abi_versions(1..=2);
+        let maybe_mod = rt_ref.load_module_from_bytes("abi_v2.wasm", &wasm_bytes);
+        assert!(maybe_mod.is_ok());
+
+        let legacy_bytes = wat::parse_str("(module)").expect("Failed to parse WAT");
+        let maybe_mod = rt_ref.load_module_from_bytes("legacy.wasm", &legacy_bytes);
+        assert!(maybe_mod.is_ok());
+    }
+
+    #[test]
+    fn test_instance_scratch_cleared_after_call() {
+        let rt = create_runtime();
+        let rt_ref = rt.borrow();
+
+        let host_func0 = ZenHostFuncDesc {
+            name: "get_host_number".to_string(),
+            arg_types: vec![ZenValueType::I32, ZenValueType::I32],
+            ret_types: vec![ZenValueType::I32],
+            ptr: count_host_calls as *const cty::c_void,
+        };
+        let host_funcs = vec![host_func0];
+        rt_ref
+            .create_host_module("env", host_funcs.iter(), true)
+            .expect("Failed to create host module");
+
+        let wasm_bytes = wat::parse_str(
+            r#"
+            (module
+                (import "env" "get_host_number" (func $get_host_number (param i32 i32) (result i32)))
+                (func (export "test") (result i32)
+                    (drop (call $get_host_number (i32.const 0) (i32.const 0)))
+                    (call $get_host_number (i32.const 0) (i32.const 0))
+                )
+            )
+        "#,
+        )
+        .expect("Failed to parse WAT");
+        let wasm_mod = rt_ref
+            .load_module_from_bytes("scratch.wasm", &wasm_bytes)
+            .expect("Failed to load WASM module");
+        let isolation = rt_ref.new_isolation().expect("Failed to create isolation");
+        let inst = wasm_mod
+            .new_instance(isolation, 100000000)
+            .expect("Failed to create WASM instance");
+
+        for _ in 0..2 {
+            let results = inst
+                .call_wasm_func("test", &[])
+                .expect("Failed to call test");
+            assert_eq!("2".to_string(), results[0].to_string());
+        }
+    }
+
+    #[test]
+    fn test_instance_nested_scratch_borrow() {
+        let rt = create_runtime();
+        let rt_ref = rt.borrow();
+
+        let host_func0 = ZenHostFuncDesc {
+            name: "get_host_number".to_string(),
+            arg_types: vec![ZenValueType::I32, ZenValueType::I32],
+            ret_types: vec![ZenValueType::I32],
+            ptr: nested_scratch_borrow as *const cty::c_void,
+        };
+        let host_funcs = vec![host_func0];
+        rt_ref
+            .create_host_module("env", host_funcs.iter(), true)
+            .expect("Failed to create host module");
+
+        let wasm_path = "./example/demo_hostapi.0.wasm";
+        let wasm_bytes = fs::read(wasm_path).unwrap();
+        let wasm_mod = rt_ref
+            .load_module_from_bytes(wasm_path, &wasm_bytes)
+            .expect("Failed to load WASM module");
+        let isolation = rt_ref.new_isolation().expect("Failed to create isolation");
+        let inst = wasm_mod
+            .new_instance(isolation, 100000000)
+            .expect("Failed to create WASM instance");
+
+        let args = vec![ZenValue::ZenI32Value(2), ZenValue::ZenI32Value(3)];
+        let results = inst
+            .call_wasm_func("test", &args)
+            .expect("Failed to call test");
+        assert_eq!("1".to_string(), results[0].to_string());
+    }
+
+    #[test]
+    fn test_minimal_host_module() {
+        let rt = create_runtime();
+        let rt_ref = rt.borrow();
+        let wasm_path = "./example/demo_hostapi.0.wasm";
+        let wasm_bytes = fs::read(wasm_path).unwrap();
+
+        let host_funcs = vec![
+            ZenHostFuncDesc {
+                name: "get_host_number".to_string(),
+                arg_types: vec![ZenValueType::I32, ZenValueType::I32],
+                ret_types: vec![ZenValueType::I32],
+                ptr: get_host_number as *const cty::c_void,
+            },
+            ZenHostFuncDesc {
+                name: "count_host_calls".to_string(),
+                arg_types: vec![ZenValueType::I32, ZenValueType::I32],
+                ret_types: vec![ZenValueType::I32],
+                ptr: count_host_calls as *const cty::c_void,
+            },
+        ];
+        let (_host_module, usage) = rt_ref
+            .create_minimal_host_module("env", host_funcs.iter(), &wasm_bytes)
+            .expect("Failed to create minimal host module");
+        let used: Vec<&str> = usage.used.iter().map(|desc| desc.name.as_str()).collect();
+        assert_eq!(vec!["get_host_number"], used);
+        assert_eq!(vec!["count_host_calls".to_string()], usage.unused);
+        assert!(usage.missing.is_empty());
+
+        let wasm_mod = rt_ref
+            .load_module_from_bytes(wasm_path, &wasm_bytes)
+            .expect("Failed to load module");
+        let isolation = rt_ref.new_isolation().expect("Failed to create isolation");
+        let inst = wasm_mod
+            .new_instance(isolation, 100000000)
+            .expect("Failed to create instance");
+        let args = vec![ZenValue::ZenI32Value(2), ZenValue::ZenI32Value(3)];
+        let results = inst
+            .call_wasm_func("test", &args)
+            .expect("Failed to call test");
+        assert_eq!("100102".to_string(), results[0].to_string());
+    }
+
+    #[test]
+    fn test_describe_host_functions() {
+        let rt = create_runtime();
+        let rt_ref = rt.borrow();
+        let host_funcs = vec![ZenHostFuncDesc {
+            name: "get_host_number".to_string(),
+            arg_types: vec![ZenValueType::I32, ZenValueType::I32],
+            ret_types: vec![ZenValueType::I32],
+            ptr: get_host_number as *const cty::c_void,
+        }];
+        rt_ref
+            .create_host_module("env", host_funcs.iter(), true)
+            .expect("Failed to create host module");
+
+        let signatures = rt_ref.host_func_signatures();
+        assert_eq!(1, signatures.len());
+        assert_eq!("env", signatures[0].module);
+        assert_eq!(vec![ZenValueType::I32], signatures[0].results);
+        assert_eq!(
+            r#"[{"module":"env","name":"get_host_number","params":["i32","i32"],"results":["i32"]}]"#,
+            rt_ref.describe_host_functions()
+        );
+    }
+
+    #[cfg(feature = "experimental")]
+    #[test]
+    fn test_shared_buffer() {
+        use crate::core::shared_buffer::{
+            set_shared_buffer, shared_buffer_host_funcs, SHARED_BUFFER_MODULE,
+        };
+
+        let rt = create_runtime();
+        let rt_ref = rt.borrow();
+        rt_ref
+            .create_host_module(
+                SHARED_BUFFER_MODULE,
+                shared_buffer_host_funcs::<i64>().iter(),
+                true,
+            )
+            .expect("Failed to create shared buffer host module");
+        let host_funcs = vec![ZenHostFuncDesc {
+            name: "get_host_number".to_string(),
+            arg_types: vec![ZenValueType::I32, ZenValueType::I32],
+            ret_types: vec![ZenValueType::I32],
+            ptr: exposed_buffer_len as *const cty::c_void,
+        }];
+        rt_ref
+            .create_host_module("env", host_funcs.iter(), true)
+            .expect("Failed to create host module");
+
+        let parent_bytes = wat::parse_str(
+            r#"
+            (module
+                (import "dtvm_ext" "shared_buffer_expose" (func $expose (param i32 i32)))
+                (import "env" "get_host_number" (func $exposed_len (param i32 i32) (result i32)))
+                (memory 1)
+                (data (i32.const 16) "hello")
+                (func (export "test") (result i32)
+                    (call $expose (i32.const 16) (i32.const 5))
+                    (call $exposed_len (i32.const 0) (i32.const 0))
+                )
+            )
+        "#,
+        )
+        .expect("Failed to parse WAT");
+        let child_bytes = wat::parse_str(
+            r#"
+            (module
+                (import "dtvm_ext" "shared_buffer_size" (func $size (result i32)))
+                (import "dtvm_ext" "shared_buffer_read" (func $read (param i32 i32 i32)))
+                (memory 1)
+                (func (export "test") (result i32)
+                    (call $read (i32.const 1) (i32.const 0) (i32.const 2))
+                    (i32.add
+                        (i32.mul (call $size) (i32.const 1000))
+                        (i32.load16_u (i32.const 0)))
+                )
+            )
+        "#,
+        )
+        .expect("Failed to parse WAT");
+
+        let parent_mod = rt_ref
+            .load_module_from_bytes("parent.wasm", &parent_bytes)
+            .expect("Failed to load parent module");
+        let parent = parent_mod
+            .new_instance(rt_ref.new_isolation().unwrap(), 100000000)
+            .expect("Failed to create parent instance");
+        let results = parent
+            .call_wasm_func("test", &[])
+            .expect("Failed to call parent");
+        assert_eq!("5".to_string(), results[0].to_string());
+
+        let child_mod = rt_ref
+            .load_module_from_bytes("child.wasm", &child_bytes)
+            .expect("Failed to load child module");
+        let child = child_mod
+            .new_instance(rt_ref.new_isolation().unwrap(), 100000000)
+            .expect("Failed to create child instance");
+        set_shared_buffer(&child, b"hello".to_vec().into());
+        let results = child
+            .call_wasm_func("test", &[])
+            .expect("Failed to call child");
+        // size 5, bytes "el" read as a little-endian u16
+        assert_eq!(
+            (5 * 1000 + u16::from_le_bytes(*b"el") as i32).to_string(),
+            results[0].to_string()
+        );
+
+        // the received buffer is gone after the call
+        let results = child.call_wasm_func("test", &[]);
+        assert!(results.is_err());
+    }
+
+    #[test]
+    fn test_closure_host_function() {
+        let rt = create_runtime();
+        let rt_ref = rt.borrow();
+
+        let calls = Rc::new(std::cell::Cell::new(0));
+        let calls_in_host = calls.clone();
+        let host_func0 = rt_ref
+            .register_host_closure(
+                "env",
+                "get_host_number",
+                move |inst: &ZenInstance<i64>, a: i32, b: i32| -> Result<i32, HostFunctionError> {
+                    calls_in_host.set(calls_in_host.get() + 1);
+                    if a < 0 {
+                        return Err(HostFunctionError::Abort);
+                    }
+                    Ok(*inst.get_extra_ctx() as i32 + a * 10 + b)
+                },
+            )
+            .expect("Failed to register host closure");
+        assert_eq!(
+            vec![ZenValueType::I32, ZenValueType::I32],
+            host_func0.arg_types
+        );
+        assert_eq!(vec![ZenValueType::I32], host_func0.ret_types);
+        let host_funcs = vec![host_func0];
+        rt_ref
+            .create_host_module("env", host_funcs.iter(), true)
+            .expect("Failed to create host module");
+
+        let wasm_path = "./example/demo_hostapi.0.wasm";
+        let wasm_bytes = fs::read(wasm_path).unwrap();
+        let wasm_mod = rt_ref
+            .load_module_from_bytes(wasm_path, &wasm_bytes)
+            .expect("Failed to load module");
+        let isolation = rt_ref.new_isolation().expect("Failed to create isolation");
+        let inst = wasm_mod
+            .new_instance_with_context(isolation, 100000000, 1000i64)
+            .expect("Failed to create instance");
+
+        let args = vec![ZenValue::ZenI32Value(2), ZenValue::ZenI32Value(3)];
+        let results = inst
+            .call_wasm_func("test", &args)
+            .expect("Failed to call test");
+        assert_eq!("1023".to_string(), results[0].to_string());
+
+        let args = vec![ZenValue::ZenI32Value(-1), ZenValue::ZenI32Value(3)];
+        assert!(inst.call_wasm_func("test", &args).is_err());
+        assert_eq!(2, calls.get());
+
+        assert!(inst.host_call_metrics().is_none());
+        inst.enable_host_call_metrics();
+        let args = vec![ZenValue::ZenI32Value(2), ZenValue::ZenI32Value(3)];
+        inst.call_wasm_func("test", &args)
+            .expect("Failed to call test");
+        inst.call_wasm_func("test", &args)
+            .expect("Failed to call test");
+        let metrics = inst
+            .disable_host_call_metrics()
+            .expect("Metrics should be enabled");
+        assert_eq!(2, metrics.get("get_host_number").unwrap().calls);
+        assert!(inst.host_call_metrics().is_none());
+
+        // the closure aborts instances created with another context type
+        let other = wasm_mod
+            .new_instance_with_context(rt_ref.new_isolation().unwrap(), 100000000, 1000u32)
+            .expect("Failed to create instance");
+        assert!(other.call_wasm_func("test", &args).is_err());
+        assert_eq!(4, calls.get());
+
+        // closures are keyed by host module and name, closures of one type can be registered
+        // under several names
+        let constant = |value: i32| {
+            move |_inst: &ZenInstance<i64>| -> Result<i32, HostFunctionError> { Ok(value) }
+        };
+        assert!(rt_ref
+            .register_host_closure("env", "get_host_number", constant(0))
+            .is_err());
+        rt_ref
+            .register_host_closure("env", "first_constant", constant(1))
+            .expect("Failed to register host closure");
+        rt_ref
+            .register_host_closure("env", "second_constant", constant(2))
+            .expect("Failed to register host closure");
+    }
+
+    #[test]
+    fn test_closure_host_function_panic() {
+        let rt = create_runtime();
+        let rt_ref = rt.borrow();
+        let host_func = rt_ref
+            .register_host_closure(
+                "env",
+                "get_host_number",
+                |_inst: &ZenInstance<i64>, a: i32, _b: i32| -> Result<i32, HostFunctionError> {
+                    if a < 0 {
+                        panic!("negative host number {a}");
+                    }
+                    Ok(a)
+                },
+            )
+            .expect("Failed to register host closure");
+        rt_ref
+            .create_host_module("env", [host_func].iter(), true)
+            .expect("Failed to create host module");
+        let wasm_path = "./example/demo_hostapi.0.wasm";
+        let wasm_bytes = fs::read(wasm_path).unwrap();
+        let wasm_mod = rt_ref
+            .load_module_from_bytes(wasm_path, &wasm_bytes)
+            .expect("Failed to load module");
+        let isolation = rt_ref.new_isolation().expect("Failed to create isolation");
+        let inst = wasm_mod
+            .new_instance(isolation, 100000000)
+            .expect("Failed to create instance");
+
+        // the panic is caught in the trampoline and the instance stays usable
+        let args = vec![ZenValue::ZenI32Value(-1), ZenValue::ZenI32Value(3)];
+        assert!(inst.call_wasm_func("test", &args).is_err());
+        assert_eq!(
+            Some("negative host number -1".to_string()),
+            inst.take_host_panic()
+        );
+        assert_eq!(None, inst.take_host_panic());
+
+        assert_eq!(
+            Some(ZenCallError::HostPanic(
+                "negative host number -2".to_string()
+            )),
+            inst.call_wasm_func_typed(
+                "test",
+                &[ZenValue::ZenI32Value(-2), ZenValue::ZenI32Value(0)]
+            )
+            .err()
+        );
+
+        let args = vec![ZenValue::ZenI32Value(2), ZenValue::ZenI32Value(3)];
+        assert!(inst.call_wasm_func("test", &args).is_ok());
+        assert_eq!(None, inst.take_host_panic());
+    }
+
+    #[test]
+    fn test_instance_pool() {
+        let rt = create_runtime();
+        let rt_ref = rt.borrow();
+        let host_funcs = vec![ZenHostFuncDesc {
+            name: "get_host_number".to_string(),
+            arg_types: vec![ZenValueType::I32, ZenValueType::I32],
+            ret_types: vec![ZenValueType::I32],
+            ptr: get_host_number as *const cty::c_void,
+        }];
+        rt_ref
+            .create_host_module("env", host_funcs.iter(), true)
+            .expect("Failed to create host module");
+
+        let pool = InstancePool::<i64>::new(rt_ref.clone(), 1);
+        let wasm_bytes = fs::read("./example/demo_hostapi.0.wasm").unwrap();
+        pool.load_module("demo", &wasm_bytes)
+            .expect("Failed to load module");
+        pool.load_module("demo", &wasm_bytes)
+            .expect("Failed to load cached module");
+        assert_eq!(1, rt_ref.live_resources().modules);
+        assert!(pool
+            .load_module("demo", &wasm_bytes[..wasm_bytes.len() - 1])
+            .is_err());
+        assert!(pool.acquire("missing", 1000, 0).is_err());
+
+        let args = vec![ZenValue::ZenI32Value(2), ZenValue::ZenI32Value(3)];
+        let inst = pool
+            .acquire("demo", 100000000, 1)
+            .expect("Failed to acquire");
+        let first_ptr = inst.ptr;
+        let results = inst
+            .call_wasm_func("test", &args)
+            .expect("Failed to call test");
+        assert_eq!("100102".to_string(), results[0].to_string());
+        let other = pool
+            .acquire("demo", 100000000, 2)
+            .expect("Failed to acquire");
+        assert_ne!(first_ptr, other.ptr);
+        drop(inst);
+        drop(other);
+        // only one idle instance is kept
+        assert_eq!(1, pool.idle_count("demo"));
+        assert_eq!(1, rt_ref.live_resources().instances);
+
+        let inst = pool.acquire("demo", 500, 3).expect("Failed to acquire");
+        assert_eq!(first_ptr, inst.ptr);
+        assert_eq!(500, inst.get_gas_left());
+        assert_eq!(3, *inst.get_extra_ctx());
+        assert_eq!(0, pool.idle_count("demo"));
+        drop(inst);
+
+        // an instance failing a reset hook is replaced by a fresh one
+        pool.add_reset_hook(|_inst| Err("dirty".to_string()));
+        let inst = pool.acquire("demo", 500, 4).expect("Failed to acquire");
+        assert_eq!(0, pool.idle_count("demo"));
+        assert_eq!(1, rt_ref.live_resources().instances);
+        let detached = inst.detach();
+        assert_eq!(0, pool.idle_count("demo"));
+
+        pool.evict_module("demo");
+        assert!(pool.module("demo").is_none());
+        drop(detached);
+        assert_eq!(0, rt_ref.live_resources().instances);
+    }
+
+    #[test]
+    fn test_instance_pool_after_trap() {
+        let rt = create_runtime();
+        let rt_ref = rt.borrow();
+        let host_func = rt_ref
+            .register_host_closure(
+                "env",
+                "get_host_number",
+                |_inst: &ZenInstance<i64>, a: i32, _b: i32| -> Result<i32, HostFunctionError> {
+                    if a < 0 {
+                        panic!("negative host number {a}");
+                    }
+                    Ok(a)
+                },
+            )
+            .expect("Failed to register host closure");
+        rt_ref
+            .create_host_module("env", [host_func].iter(), true)
+            .expect("Failed to create host module");
+
+        let pool = InstancePool::<i64>::new(rt_ref.clone(), 1);
+        let wasm_bytes = fs::read("./example/demo_hostapi.0.wasm").unwrap();
+        pool.load_module("demo", &wasm_bytes)
+            .expect("Failed to load module");
+
+        // a trapped instance is not recycled
+        let inst = pool
+            .acquire("demo", 100000000, 1)
+            .expect("Failed to acquire");
+        let trapped_ptr = inst.ptr;
+        let args = vec![ZenValue::ZenI32Value(-1), ZenValue::ZenI32Value(3)];
+        assert!(inst.call_wasm_func("test", &args).is_err());
+        drop(inst);
+        assert_eq!(0, pool.idle_count("demo"));
+        assert_eq!(0, rt_ref.live_resources().instances);
+
+        // a recycled instance does not carry the state of its previous user
+        let inst = pool
+            .acquire("demo", 100000000, 2)
+            .expect("Failed to acquire");
+        assert_ne!(trapped_ptr, inst.ptr);
+        let first_ptr = inst.ptr;
+        inst.enable_host_call_metrics();
+        let args = vec![ZenValue::ZenI32Value(2), ZenValue::ZenI32Value(3)];
+        assert!(inst.call_wasm_func("test", &args).is_ok());
+        *inst.scratch::<i32>() = 7;
+        drop(inst);
+        assert_eq!(1, pool.idle_count("demo"));
+
+        let inst = pool
+            .acquire("demo", 100000000, 3)
+            .expect("Failed to acquire");
+        assert_eq!(first_ptr, inst.ptr);
+        assert_eq!(None, inst.error_code());
+        assert_eq!(None, inst.take_host_panic());
+        assert!(inst.host_call_metrics().is_none());
+        assert_eq!(0, *inst.scratch::<i32>());
+    }
+
+    #[test]
+    fn test_runtime_shutdown_and_live_resources() {
+        let rt = create_runtime();
+        let rt_ref = rt.borrow();
+        assert_eq!(ZenRuntimeResources::default(), rt_ref.live_resources());
+
+        let wasm_bytes =
+            wat::parse_str(r#"(module (func (export "test") (result i32) i32.const 1))"#)
+                .expect("Failed to parse WAT");
+        let wasm_mod = rt_ref
+            .load_module_from_bytes("shutdown.wasm", &wasm_bytes)
+            .expect("Failed to load module");
+        let isolation = rt_ref.new_isolation().expect("Failed to create isolation");
+        let inst = wasm_mod
+            .new_instance(isolation, 100000000)
+            .expect("Failed to create instance");
+        assert_eq!(
+            ZenRuntimeResources {
+                modules: 1,
+                isolations: 1,
+                instances: 1
+            },
+            rt_ref.live_resources()
+        );
+
+        let err = rt_ref.shutdown().unwrap_err();
+        assert!(err.contains("1 instances still alive"));
+        assert!(rt_ref.is_shut_down());
+        assert!(inst.call_wasm_func("test", &[]).is_err());
+        assert!(rt_ref.new_isolation().is_err());
+        assert!(rt_ref
+            .load_module_from_bytes("shutdown.wasm", &wasm_bytes)
+            .is_err());
+
+        // the instance releases its isolation, then the module goes
+        drop(inst);
+        assert_eq!(
+            ZenRuntimeResources {
+                modules: 1,
+                isolations: 0,
+                instances: 0
+            },
+            rt_ref.live_resources()
+        );
+        drop(wasm_mod);
+        assert_eq!(ZenRuntimeResources::default(), rt_ref.live_resources());
+        assert!(rt_ref.shutdown().is_ok());
+    }
+
+    #[cfg(feature = "failure-injection")]
+    #[test]
+    fn test_wasm_addr_validation_failure_injection() {
+        let rt = create_runtime();
+        let rt_ref = rt.borrow();
+        let host_funcs = vec![ZenHostFuncDesc {
+            name: "get_host_number".to_string(),
+            arg_types: vec![ZenValueType::I32, ZenValueType::I32],
+            ret_types: vec![ZenValueType::I32],
+            ptr: copy_word as *const cty::c_void,
+        }];
+        rt_ref
+            .create_host_module("env", host_funcs.iter(), true)
+            .expect("Failed to create host module");
+
+        let wasm_bytes = wat::parse_str(
+            r#"
+            (module
+                (import "env" "get_host_number" (func $copy_word (param i32 i32) (result i32)))
+                (memory 1)
+                (func (export "test") (param i32 i32) (result i32)
+                    (call $copy_word (local.get 0) (local.get 1))
+                )
+            )
+        "#,
+        )
+        .expect("Failed to parse WAT");
+        let wasm_mod = rt_ref
+            .load_module_from_bytes("failure_injection.wasm", &wasm_bytes)
+            .expect("Failed to load module");
+        let isolation = rt_ref.new_isolation().expect("Failed to create isolation");
+        let inst = wasm_mod
+            .new_instance(isolation, 100000000)
+            .expect("Failed to create instance");
+        let args = vec![ZenValue::ZenI32Value(0), ZenValue::ZenI32Value(64)];
+        assert!(inst.call_wasm_func("test", &args).is_ok());
+
+        inst.fail_wasm_addr_validation(66..67);
+        assert!(inst.validate_wasm_addr(0, 4));
+        assert!(!inst.validate_wasm_addr(64, 4));
+        assert!(inst.call_wasm_func("test", &args).is_err());
+
+        inst.clear_wasm_addr_failures();
+        assert!(inst.call_wasm_func("test", &args).is_ok());
+    }
+
+    #[cfg(feature = "otel")]
+    #[test]
+    fn test_otel_spans() {
+        use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+        use opentelemetry_sdk::trace::TracerProvider;
+
+        let exporter = InMemorySpanExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        opentelemetry::global::set_tracer_provider(provider);
+
+        let rt = create_runtime();
+        let rt_ref = rt.borrow();
+        // the host function of one instance calls into another one
+        let callee: Rc<RefCell<Option<Rc<ZenInstance<i64>>>>> = Rc::new(RefCell::new(None));
+        let host_callee = callee.clone();
+        let host_func = rt_ref
+            .register_host_closure_in_category(
+                "env",
+                "demo",
+                "get_host_number",
+                move |_inst: &ZenInstance<i64>, a: i32, b: i32| -> Result<i32, HostFunctionError> {
+                    if a > 0 {
+                        let callee = host_callee.borrow().clone().unwrap();
+                        callee
+                            .call_wasm_func(
+                                "test",
+                                &[ZenValue::ZenI32Value(0), ZenValue::ZenI32Value(b)],
+                            )
+                            .map_err(|_| HostFunctionError::Abort)?;
+                    }
+                    Ok(a + b)
+                },
+            )
+            .expect("Failed to register host closure");
+        rt_ref
+            .create_host_module("env", [host_func].iter(), true)
+            .expect("Failed to create host module");
+        let wasm_path = "./example/demo_hostapi.0.wasm";
+        let wasm_bytes = fs::read(wasm_path).unwrap();
+        let wasm_mod = rt_ref
+            .load_module_from_bytes(wasm_path, &wasm_bytes)
+            .expect("Failed to load module");
+        let new_instance = || {
+            let isolation = rt_ref.new_isolation().expect("Failed to create isolation");
+            wasm_mod
+                .new_instance(isolation, 100000000)
+                .expect("Failed to create instance")
+        };
+        *callee.borrow_mut() = Some(new_instance());
+        let inst = new_instance();
+        let args = vec![ZenValue::ZenI32Value(2), ZenValue::ZenI32Value(3)];
+        inst.call_wasm_func("test", &args)
+            .expect("Failed to call test");
+
+        // other tests may record spans through the global provider concurrently
+        let spans = exporter.get_finished_spans().unwrap();
+        let attribute = |span: &opentelemetry_sdk::export::trace::SpanData, key: &str| {
+            span.attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.clone())
+        };
+        let sub_call = spans
+            .iter()
+            .find(|span| span.name == "dtvm.sub_call")
+            .expect("Missing sub call span into the other instance");
+        let host_call = spans
+            .iter()
+            .find(|span| span.span_context.span_id() == sub_call.parent_span_id)
+            .expect("Missing host call span");
+        assert_eq!("dtvm.host_call", host_call.name);
+        assert_eq!(
+            Some("demo".into()),
+            attribute(host_call, "dtvm.host_category")
+        );
+        let call = spans
+            .iter()
+            .find(|span| span.span_context.span_id() == host_call.parent_span_id)
+            .expect("Missing parent call span");
+        assert_eq!("dtvm.call", call.name);
+        assert_eq!(Some(8i64.into()), attribute(call, "dtvm.args_bytes"));
+        assert_eq!(Some(4i64.into()), attribute(call, "dtvm.results_bytes"));
+        assert_eq!(Some(100000000i64.into()), attribute(call, "dtvm.gas_limit"));
+        assert!(attribute(call, "dtvm.gas_used").is_some());
+        assert!(attribute(call, "dtvm.memory_bytes").is_some());
+    }
 }
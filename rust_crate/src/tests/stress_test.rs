@@ -0,0 +1,169 @@
+// Copyright (C) 2021-2025 the DTVM authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use std::process::{Command, Stdio};
+    use std::time::{Duration, Instant};
+
+    use crate::core::{runtime::ZenRuntime, types::ZenValue};
+    use crate::gas_metering::GasMeter;
+
+    const BIG_FUNC_COUNT: u32 = 10000;
+    // functions call their predecessor inside groups, to keep the call depth bounded
+    const CALL_GROUP_SIZE: u32 = 100;
+    const BIG_DATA_SIZE: usize = 2 * 1024 * 1024;
+    const WASM_PAGE_SIZE: usize = 64 * 1024;
+
+    const MAX_METERING_BYTES_PER_FUNC: usize = 16;
+
+    // the bench compares the big module with a module BASELINE_SCALE times smaller
+    const BASELINE_SCALE: u32 = 10;
+    // instrumentation is linear, so the big module may take BASELINE_SCALE times as long,
+    // with a factor of two for noise
+    const MAX_TIME_RATIO: u32 = 2 * BASELINE_SCALE;
+    const TIME_NOISE_FLOOR: Duration = Duration::from_millis(50);
+    // peak RSS the big module may add over the baseline: the module, its parsed form and the
+    // instrumented copy, a few times the data segment each
+    const MAX_EXTRA_PEAK_RSS_KB: i64 = 64 * 1024;
+
+    // set in the child process running stress_child, see run_isolated
+    const CHILD_SIZES_ENV: &str = "DTVM_STRESS_SIZES";
+    const CHILD_REPORT_ENV: &str = "DTVM_STRESS_REPORT";
+
+    /// Build a module with `func_count` functions and a `data_size` bytes data segment.
+    /// The exported `entry` returns the sum of the function indices of the last call group.
+    fn build_big_module(func_count: u32, data_size: usize) -> Vec<u8> {
+        let mut wat = String::from("(module\n(type $t (func (param i32) (result i32)))\n");
+        for i in 0..func_count {
+            if i % CALL_GROUP_SIZE == 0 {
+                wat.push_str(&format!(
+                    "(func $f{i} (type $t) (i32.add (local.get 0) (i32.const {i})))\n"
+                ));
+            } else {
+                wat.push_str(&format!(
+                    "(func $f{i} (type $t) (call $f{} (i32.add (local.get 0) (i32.const {i}))))\n",
+                    i - 1
+                ));
+            }
+        }
+        wat.push_str(&format!("(export \"entry\" (func $f{}))\n", func_count - 1));
+        wat.push_str(&format!("(memory {})\n", data_size / WASM_PAGE_SIZE + 1));
+        wat.push_str(&format!(
+            "(data (i32.const 0) \"{}\")\n)",
+            "x".repeat(data_size)
+        ));
+        wat::parse_str(&wat).expect("Failed to parse generated WAT")
+    }
+
+    /// Instrument and run a module of `func_count` functions, returning the instrumentation
+    /// time.
+    fn instrument_and_run(func_count: u32, data_size: usize) -> Duration {
+        let wasm_bytes = build_big_module(func_count, data_size);
+
+        let start = Instant::now();
+        let transformed =
+            GasMeter::transform_default(&wasm_bytes).expect("Transform should succeed");
+        let instrument_time = start.elapsed();
+
+        let metering_bytes = transformed.len() - wasm_bytes.len();
+        assert!(
+            metering_bytes < MAX_METERING_BYTES_PER_FUNC * func_count as usize,
+            "Metering added {} bytes",
+            metering_bytes
+        );
+
+        let rt = ZenRuntime::new(None);
+        let wasm_mod = rt
+            .load_module_from_bytes("big.wasm", &transformed)
+            .expect("Failed to load big WASM module");
+        let isolation = rt.new_isolation().expect("Failed to create isolation");
+        let inst = wasm_mod
+            .new_instance(isolation, 100000000)
+            .expect("Failed to create WASM instance");
+        let results = inst
+            .call_wasm_func("entry", &[ZenValue::ZenI32Value(0)])
+            .expect("Failed to call entry");
+        let last_group_start = func_count - CALL_GROUP_SIZE;
+        let expected_sum = (last_group_start..func_count).sum::<u32>();
+        assert_eq!(expected_sum.to_string(), results[0].to_string());
+        instrument_time
+    }
+
+    #[test]
+    fn test_instrument_big_module() {
+        instrument_and_run(BIG_FUNC_COUNT, BIG_DATA_SIZE);
+    }
+
+    /// Peak RSS of the waited for child processes, in KiB on Linux.
+    fn children_peak_rss_kb() -> i64 {
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        unsafe { libc::getrusage(libc::RUSAGE_CHILDREN, &mut usage) };
+        i64::from(usage.ru_maxrss)
+    }
+
+    /// Run stress_child in a fresh process of the test binary, so its peak RSS doesn't
+    /// include the other tests. Returns the instrumentation time and the peak RSS of all
+    /// children run so far.
+    fn run_isolated(func_count: u32, data_size: usize) -> (Duration, i64) {
+        let report =
+            std::env::temp_dir().join(format!("dtvm_stress_{}_{func_count}", std::process::id()));
+        let status = Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "tests::stress_test::tests::stress_child",
+                "--ignored",
+                "--test-threads=1",
+            ])
+            .env(CHILD_SIZES_ENV, format!("{func_count}:{data_size}"))
+            .env(CHILD_REPORT_ENV, &report)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .expect("Failed to run the stress child process");
+        assert!(status.success(), "Stress child failed: {status}");
+        let nanos = std::fs::read_to_string(&report).expect("Missing stress child report");
+        let _ = std::fs::remove_file(&report);
+        let instrument_time = Duration::from_nanos(nanos.trim().parse().unwrap());
+        (instrument_time, children_peak_rss_kb())
+    }
+
+    /// Child side of run_isolated, does nothing when run directly.
+    #[test]
+    #[ignore]
+    fn stress_child() {
+        let Ok(sizes) = std::env::var(CHILD_SIZES_ENV) else {
+            return;
+        };
+        let (func_count, data_size) = sizes.split_once(':').unwrap();
+        let instrument_time =
+            instrument_and_run(func_count.parse().unwrap(), data_size.parse().unwrap());
+        let report = std::env::var(CHILD_REPORT_ENV).unwrap();
+        std::fs::write(report, instrument_time.as_nanos().to_string()).unwrap();
+    }
+
+    /// Time and memory of instrumenting the big module, relative to a smaller one. Run with
+    /// `cargo test --release -- --ignored bench_instrument_big_module`.
+    #[test]
+    #[ignore]
+    fn bench_instrument_big_module() {
+        let (baseline_time, baseline_rss) = run_isolated(
+            BIG_FUNC_COUNT / BASELINE_SCALE,
+            BIG_DATA_SIZE / BASELINE_SCALE as usize,
+        );
+        let (big_time, big_rss) = run_isolated(BIG_FUNC_COUNT, BIG_DATA_SIZE);
+
+        assert!(
+            big_time <= baseline_time * MAX_TIME_RATIO + TIME_NOISE_FLOOR,
+            "Instrumentation took {:?}, {:?} for the baseline",
+            big_time,
+            baseline_time
+        );
+        assert!(
+            big_rss - baseline_rss < MAX_EXTRA_PEAK_RSS_KB,
+            "Peak RSS {} KiB, {} KiB for the baseline",
+            big_rss,
+            baseline_rss
+        );
+    }
+}
@@ -2014,6 +2014,9 @@ void BaseInterpreterImpl::interpret() {
 #endif
         if (FuncIdx == Mod->getGasFuncIdx()) {
           uint64_t Delta = Frame->valuePop<uint64_t>(ValStackPtr);
+          if (ModInst->isInterruptRequested()) {
+            throw getError(ErrorCode::ExecutionInterrupted);
+          }
           uint64_t GasLeft = ModInst->getGas();
           if (GasLeft < Delta) {
             ModInst->setGas(0);
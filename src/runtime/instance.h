@@ -8,6 +8,7 @@
 #include "common/traphandler.h"
 #include "runtime/module.h"
 #include "utils/backtrace.h"
+#include <atomic>
 #ifdef ZEN_ENABLE_VIRTUAL_STACK
 #include "utils/virtual_stack.h"
 #include <queue>
@@ -260,6 +261,18 @@ public:
   uint64_t getGas() const { return Gas; }
   void setGas(uint64_t NewGas) { Gas = NewGas; }
 
+  // Ask the running call to stop, may be called from any thread. Only the
+  // interpreter checks the request, at every gas charge.
+  void requestInterrupt() {
+    InterruptRequested.store(true, std::memory_order_relaxed);
+  }
+  void clearInterruptRequest() {
+    InterruptRequested.store(false, std::memory_order_relaxed);
+  }
+  bool isInterruptRequested() const {
+    return InterruptRequested.load(std::memory_order_relaxed);
+  }
+
   void *getCustomData() { return CustomData; }
   void setCustomData(void *NewCustomData) { CustomData = NewCustomData; }
 
@@ -358,6 +371,9 @@ private:
 
   bool DataSegsInited = false;
 
+  // set by requestInterrupt from other threads
+  std::atomic<bool> InterruptRequested{false};
+
 #ifdef ZEN_ENABLE_VIRTUAL_STACK
   // one instance maybe called by hostapi( instanceA -> hostapi -> instanceA )
   std::queue<utils::VirtualStackInfo *> VirtualStacks;
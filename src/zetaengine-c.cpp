@@ -551,6 +551,17 @@ bool ZenGetInstanceError(ZenInstanceRef Instance, char *ErrBuf,
   return true;
 }
 
+bool ZenGetInstanceErrorCode(ZenInstanceRef Instance, uint32_t *ErrCode) {
+  ZEN_ASSERT(Instance);
+  ZEN_ASSERT(ErrCode);
+  zen::runtime::Instance *Inst = unwrap(Instance);
+  if (!Inst->hasError()) {
+    return false;
+  }
+  *ErrCode = (uint32_t)Inst->getError().getCode();
+  return true;
+}
+
 void ZenClearInstanceError(ZenInstanceRef Instance) {
   ZEN_ASSERT(Instance);
   zen::runtime::Instance *Inst = unwrap(Instance);
@@ -583,6 +594,15 @@ uint32_t ZenGetAppMemOffset(ZenInstanceRef Instance, void *HostAddr) {
   return Inst->getMemoryOffset(HostAddr);
 }
 
+uint64_t ZenGetInstanceMemorySize(ZenInstanceRef Instance) {
+  ZEN_ASSERT(Instance);
+  zen::runtime::Instance *Inst = unwrap(Instance);
+  if (!Inst->hasMemory()) {
+    return 0;
+  }
+  return Inst->getDefaultMemoryInst().MemSize;
+}
+
 void ZenSetInstanceCustomData(ZenInstanceRef Instance, void *CustomData) {
   ZEN_ASSERT(Instance);
   zen::runtime::Instance *Inst = unwrap(Instance);
@@ -607,6 +627,18 @@ void ZenSetInstanceGasLeft(ZenInstanceRef Instance, uint64_t NewGas) {
   Inst->setGas(NewGas);
 }
 
+void ZenInterruptInstance(ZenInstanceRef Instance) {
+  ZEN_ASSERT(Instance);
+  zen::runtime::Instance *Inst = unwrap(Instance);
+  Inst->requestInterrupt();
+}
+
+void ZenClearInstanceInterrupt(ZenInstanceRef Instance) {
+  ZEN_ASSERT(Instance);
+  zen::runtime::Instance *Inst = unwrap(Instance);
+  Inst->clearInterruptRequest();
+}
+
 void ZenSetInstanceExceptionByHostapi(ZenInstanceRef Instance,
                                       uint32_t ErrorCode) {
   ZEN_ASSERT(Instance);
@@ -628,6 +660,15 @@ uint32_t ZenGetErrCodeGasLimitExceeded() {
 uint32_t ZenGetErrCodeOutOfBoundsMemory() {
   return (uint32_t)zen::common::ErrorCode::OutOfBoundsMemory;
 }
+uint32_t ZenGetErrCodeOutOfGas() {
+  // goes through getError to apply the DWasm mapping like the trap does
+  using namespace zen::common;
+  return (uint32_t)getError(ErrorCode::GasLimitExceeded).getCode();
+}
+uint32_t ZenGetErrCodeInterrupted() {
+  using namespace zen::common;
+  return (uint32_t)getError(ErrorCode::ExecutionInterrupted).getCode();
+}
 
 void ZenInstanceExit(ZenInstanceRef Instance, int32_t ExitCode) {
   ZEN_ASSERT(Instance);
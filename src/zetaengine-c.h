@@ -182,6 +182,11 @@ bool ZenGetInstanceError(ZenInstanceRef Instance, char *ErrBuf,
 
 void ZenClearInstanceError(ZenInstanceRef Instance);
 
+/// \return true if the instance has an error, whose code (as reported in the
+/// error message, i.e. after the DWasm mapping when enabled) is stored in
+/// ErrCode
+bool ZenGetInstanceErrorCode(ZenInstanceRef Instance, uint32_t *ErrCode);
+
 bool ZenValidateHostMemAddr(ZenInstanceRef Instance, void *HostAddr,
                             uint32_t Size);
 
@@ -192,6 +197,9 @@ void *ZenGetHostMemAddr(ZenInstanceRef Instance, uint32_t Offset);
 
 uint32_t ZenGetAppMemOffset(ZenInstanceRef Instance, void *HostAddr);
 
+// size in bytes of the default linear memory, 0 when the instance has none
+uint64_t ZenGetInstanceMemorySize(ZenInstanceRef Instance);
+
 void ZenSetInstanceCustomData(ZenInstanceRef Instance, void *CustomData);
 
 void *ZenGetInstanceCustomData(ZenInstanceRef Instance);
@@ -200,6 +208,13 @@ uint64_t ZenGetInstanceGasLeft(ZenInstanceRef Instance);
 
 void ZenSetInstanceGasLeft(ZenInstanceRef Instance, uint64_t NewGas);
 
+// Ask the running call of the instance to stop with an "execution interrupted"
+// error. Safe to call from any thread while the instance is alive. Only the
+// interpreter honors the request, at the gas charges of a metered module.
+void ZenInterruptInstance(ZenInstanceRef Instance);
+
+void ZenClearInstanceInterrupt(ZenInstanceRef Instance);
+
 // param ErrorCode: zen::common::ErrorCode
 void ZenSetInstanceExceptionByHostapi(ZenInstanceRef Instance,
                                       uint32_t ErrorCode);
@@ -207,6 +222,10 @@ void ZenSetInstanceExceptionByHostapi(ZenInstanceRef Instance,
 uint32_t ZenGetErrCodeEnvAbort();
 uint32_t ZenGetErrCodeGasLimitExceeded();
 uint32_t ZenGetErrCodeOutOfBoundsMemory();
+// code reported by ZenGetInstanceErrorCode when the instance ran out of gas
+uint32_t ZenGetErrCodeOutOfGas();
+// code reported by ZenGetInstanceErrorCode when the call was interrupted
+uint32_t ZenGetErrCodeInterrupted();
 
 void ZenInstanceExit(ZenInstanceRef Instance, int32_t ExitCode);
 int32_t ZenGetInstanceExitCode(ZenInstanceRef Instance);